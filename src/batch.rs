@@ -0,0 +1,100 @@
+use std::path::{Path, PathBuf};
+
+use druid::{Data, Lens};
+
+use crate::formats::{is_kfr_file, is_png_file};
+use crate::progress::{RenderProgress, RenderStage};
+use crate::sequence::SequenceFormat;
+
+#[derive(Data, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BatchStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl BatchStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BatchStatus::Queued => "QUEUED",
+            BatchStatus::Running => "RUNNING",
+            BatchStatus::Done => "DONE",
+            BatchStatus::Failed => "FAILED",
+            BatchStatus::Cancelled => "CANCELLED",
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self, BatchStatus::Done | BatchStatus::Failed | BatchStatus::Cancelled)
+    }
+}
+
+/// A location file waiting to be rendered by the batch queue.
+#[derive(Data, Clone, PartialEq, Lens, Debug)]
+pub struct BatchJob {
+    pub id: usize,
+    pub location: String,
+    pub status: BatchStatus,
+    /// The progress of the render while the job is running
+    pub progress: RenderProgress,
+    /// The reason the job failed
+    pub error: String,
+}
+
+impl BatchJob {
+    pub fn new(id: usize, location: &Path) -> Self {
+        BatchJob {
+            id,
+            location: location.display().to_string(),
+            status: BatchStatus::Queued,
+            progress: RenderProgress::new(id),
+            error: String::new(),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        Path::new(&self.location).file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.location.clone())
+    }
+
+    pub fn description(&self) -> String {
+        match self.status {
+            BatchStatus::Running if self.progress.stage != RenderStage::Complete => {
+                format!("{} {:.0}%", self.progress.stage.name(), 100.0 * self.progress.progress)
+            },
+            BatchStatus::Failed => format!("{}: {}", self.status.name(), self.error),
+            _ => self.status.name().to_string()
+        }
+    }
+
+    /// The image is named after the location file.
+    pub fn output_path(&self, directory: &Path, format: SequenceFormat) -> PathBuf {
+        let stem = Path::new(&self.location).file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| format!("job_{}", self.id));
+
+        directory.join(format!("{}.{}", stem, format.extension()))
+    }
+}
+
+/// Files that can be added to the batch queue.
+pub fn is_batch_file(path: &Path) -> bool {
+    let is_toml = matches!(path.extension().and_then(|extension| extension.to_str()), Some(extension) if extension.eq_ignore_ascii_case("toml"));
+
+    is_toml || is_kfr_file(path) || is_png_file(path)
+}
+
+/// The location files in a directory, sorted by name.
+pub fn batch_files_in_directory(directory: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = std::fs::read_dir(directory).map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && is_batch_file(path))
+        .collect::<Vec<PathBuf>>();
+
+    files.sort();
+
+    Ok(files)
+}
@@ -8,36 +8,81 @@ use rust_fractal::{renderer::FractalRenderer};
 use rust_fractal::util::{extended_to_string_long, string_to_extended};
 use rust_fractal::util::data_export::ColoringType;
 
-use config::{Config, File};
-
 use std::thread;
 use std::sync::mpsc;
 use std::sync::atomic::AtomicBool;
+use std::path::Path;
 
 use rust_fractal_gui::theme::*;
-use rust_fractal_gui::render_thread::testing_renderer;
+use rust_fractal_gui::render_thread::{testing_renderer, export_thread, batch_thread};
+use rust_fractal_gui::headless::render_headless;
 use rust_fractal_gui::ui;
 use rust_fractal_gui::widgets::{FractalData};
 use rust_fractal_gui::ui::FractalType;
+use rust_fractal_gui::progress::{RenderProgress, RenderStage};
+use rust_fractal_gui::bookmarks::load_bookmarks;
+use rust_fractal_gui::pixel::LOUPE_SIZE;
+use rust_fractal_gui::sequence::SequenceFormat;
+use rust_fractal_gui::palette::Palette;
+use rust_fractal_gui::formats::apply_palette;
+use rust_fractal_gui::settings::load_settings;
+use rust_fractal_gui::notifications::Notification;
 
 pub fn main() {
+    let args = std::env::args().collect::<Vec<String>>();
+
+    // Renders a single location without a window: --headless <location> [output]
+    if args.len() > 1 && args[1] == "--headless" {
+        if args.len() < 3 {
+            eprintln!("Usage: {} --headless <location> [output]", args[0]);
+            std::process::exit(1);
+        }
+
+        let location = Path::new(&args[2]);
+
+        let output = if args.len() > 3 {
+            Path::new(&args[3]).to_path_buf()
+        } else {
+            location.with_extension("png")
+        };
+
+        if let Err(e) = render_headless(location, &output) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
     // Setup the default settings. These are stored in start.toml file
-    let mut settings = Config::default();
-    settings.merge(File::with_name("start.toml")).unwrap();
+    let (start, start_palette, warnings) = match load_settings(Path::new("start.toml")) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    if settings.get_bool("show_output").unwrap() {
+    if start.show_output {
         println!(" {:<15}| {:<15}| {:<15}| {:<6}| {:<15}| {:<15}| {:<15}| {:<6}| {:<15}", "Zoom", "Approx [ms]", "Skipped [it]", "Order", "Maximum [it]", "Iteration [ms]", "Correct [ms]", "Ref", "Frame [ms]");
     };
 
-    let shared_settings = Arc::new(Mutex::new(settings.clone()));
-    let shared_renderer = Arc::new(Mutex::new(FractalRenderer::new(settings.clone())));
+    let shared_settings = Arc::new(Mutex::new(start.clone()));
+    let shared_renderer = Arc::new(Mutex::new(FractalRenderer::new(start.to_config())));
+
+    if let Some(palette) = &start_palette {
+        apply_palette(&mut shared_renderer.lock().data_export.lock(), &start, palette);
+    }
     let shared_stop_flag = Arc::new(AtomicBool::new(false));
     let shared_repeat_flag = Arc::new(AtomicBool::new(false));
+    let shared_export_stop_flag = Arc::new(AtomicBool::new(false));
+    let shared_batch_stop_flag = Arc::new(AtomicBool::new(false));
 
-    let thread_settings = shared_settings.clone();
     let thread_renderer = shared_renderer.clone();
     let thread_stop_flag = shared_stop_flag.clone();
     let thread_repeat_flag = shared_repeat_flag.clone();
+    let thread_export_stop_flag = shared_export_stop_flag.clone();
+    let thread_batch_stop_flag = shared_batch_stop_flag.clone();
 
     let buffer = shared_renderer.lock().data_export.clone();
 
@@ -47,57 +92,80 @@ pub fn main() {
 
     let launcher = AppLauncher::with_window(window);
 
+    // Problems found before the window opened are shown in the notification log
+    let mut notifications = warnings.into_iter().map(Notification::warning).collect::<Vec<Notification>>();
+
+    let bookmarks = load_bookmarks().unwrap_or_else(|e| {
+        notifications.push(Notification::error(format!("Error reading bookmarks: {}", e)));
+        Vec::new()
+    });
+
+    // The newest notification is shown first
+    notifications.reverse();
+
     let event_sink = launcher.get_external_handle();
 
     let (sender, reciever) = mpsc::channel();
+    let (export_sender, export_reciever) = mpsc::channel();
+    let (batch_sender, batch_reciever) = mpsc::channel();
 
-    let mut center_reference_zoom = string_to_extended(&settings.get_str("zoom").unwrap());
+    let mut center_reference_zoom = string_to_extended(&start.zoom);
     center_reference_zoom.exponent += 40;
 
-    thread::spawn(move || testing_renderer(event_sink, reciever, thread_settings, thread_renderer, thread_stop_flag, thread_repeat_flag));
+    let export_event_sink = event_sink.clone();
+    let batch_event_sink = event_sink.clone();
+
+    thread::spawn(move || testing_renderer(event_sink, reciever, thread_renderer, thread_stop_flag, thread_repeat_flag));
+    thread::spawn(move || export_thread(export_event_sink, export_reciever, thread_export_stop_flag));
+    thread::spawn(move || batch_thread(batch_event_sink, batch_reciever, thread_batch_stop_flag));
 
     launcher
         .configure_env(|env, _| configure_env(env))
         .launch(FractalData {
-            image_width: settings.get_int("image_width").unwrap() as usize,
-            image_height: settings.get_int("image_height").unwrap() as usize,
-            real: settings.get_str("real").unwrap(),
-            imag: settings.get_str("imag").unwrap(),
-            zoom: settings.get_str("zoom").unwrap(),
+            image_width: start.image_width,
+            image_height: start.image_height,
+            real: start.real.clone(),
+            imag: start.imag.clone(),
+            zoom: start.zoom.clone(),
             root_zoom: "1E0".to_string(),
-            iteration_limit: settings.get_int("iterations").unwrap() as usize,
-            rotation: settings.get_float("rotate").unwrap(),
-            order: settings.get_int("approximation_order").unwrap() as usize,
+            iteration_limit: start.iterations,
+            rotation: start.rotate,
+            order: start.approximation_order,
             period: 0,
             palette_source: "default".to_string(),
-            palette_cyclic: settings.get_bool("palette_cyclic").unwrap(),
-            palette_iteration_span: settings.get_float("palette_iteration_span").unwrap(),
-            palette_offset: settings.get_float("palette_offset").unwrap(),
-            rendering_progress: 0.0,
+            palette_cyclic: start.palette_cyclic,
+            palette_iteration_span: start.palette_iteration_span,
+            palette_offset: start.palette_offset,
+            palette: Palette::from_data_export(&buffer.lock()),
+            render_progress: RenderProgress::new(0),
             root_progress: 1.0,
-            rendering_stage: 1,
-            rendering_time: 0,
+            rendering_stage: RenderStage::Reference,
             root_iteration: 64,
             root_stage: 0,
             min_valid_iterations: 1,
             max_valid_iterations: 1,
             min_iterations: 1,
             max_iterations: 1,
-            display_glitches: settings.get_bool("display_glitches").unwrap(),
-            glitch_tolerance: settings.get_float("glitch_tolerance").unwrap(),
-            glitch_percentage: settings.get_float("glitch_percentage").unwrap(),
-            iteration_interval: settings.get_int("data_storage_interval").unwrap(),
-            series_approximation_tiled: settings.get_bool("series_approximation_tiled").unwrap(),
-            series_approximation_enabled: settings.get_bool("series_approximation_enabled").unwrap(),
-            probe_sampling: settings.get_int("probe_sampling").unwrap(),
-            jitter: settings.get_bool("jitter").unwrap(),
-            jitter_factor: settings.get_float("jitter_factor").unwrap(),
-            auto_adjust_iterations: settings.get_bool("auto_adjust_iterations").unwrap(),
-            remove_centre: settings.get_bool("remove_centre").unwrap(),
+            display_glitches: start.display_glitches,
+            glitch_tolerance: start.glitch_tolerance,
+            glitch_percentage: start.glitch_percentage,
+            iteration_interval: start.data_storage_interval as i64,
+            series_approximation_tiled: start.series_approximation_tiled,
+            series_approximation_enabled: start.series_approximation_enabled,
+            probe_sampling: start.probe_sampling as i64,
+            jitter: start.jitter,
+            jitter_factor: start.jitter_factor,
+            auto_adjust_iterations: start.auto_adjust_iterations,
+            remove_centre: start.remove_centre,
             renderer: shared_renderer,
             settings: shared_settings,
             sender: Arc::new(Mutex::new(sender)),
+            render_job: 0,
             stop_flag: shared_stop_flag,
+            export_sender: Arc::new(Mutex::new(export_sender)),
+            export_stop_flag: shared_export_stop_flag,
+            batch_sender: Arc::new(Mutex::new(batch_sender)),
+            batch_stop_flag: shared_batch_stop_flag,
             repeat_flag: shared_repeat_flag,
             buffer,
             need_full_rerender: false,
@@ -105,26 +173,53 @@ pub fn main() {
             pixel_pos: [0, 0],
             pixel_iterations: 1,
             pixel_smooth: 0.0,
-            pixel_rgb: Arc::new(Mutex::new(vec![0u8; 255 * 3])),
+            pixel_distance: 0.0,
+            pixel_stripe: 0.0,
+            pixel_glitched: false,
+            pixel_real: String::new(),
+            pixel_imag: String::new(),
+            pixel_rgb: Arc::new(Mutex::new(vec![0u8; LOUPE_SIZE * LOUPE_SIZE * 3])),
             coloring_type: ColoringType::SmoothIteration,
             mouse_mode: 0,
             current_tab: 0,
-            zoom_scale_factor: settings.get_float("zoom_scale").unwrap(),
+            zoom_scale_factor: start.zoom_scale,
             root_zoom_factor: 0.5,
             center_reference_zoom: extended_to_string_long(center_reference_zoom),
             reference_count: 1,
-            stripe_scale: settings.get_float("stripe_scale").unwrap() as f32,
-            distance_transition: settings.get_float("distance_transition").unwrap() as f32,
-            distance_color: settings.get_bool("distance_color").unwrap(),
-            lighting: settings.get_bool("lighting").unwrap(),
-            lighting_direction: settings.get_float("lighting_direction").unwrap(),
-            lighting_azimuth: settings.get_float("lighting_azimuth").unwrap(),
-            lighting_opacity: settings.get_float("lighting_opacity").unwrap(),
-            lighting_ambient: settings.get_float("lighting_ambient").unwrap(),
-            lighting_diffuse: settings.get_float("lighting_diffuse").unwrap(),
-            lighting_specular: settings.get_float("lighting_specular").unwrap(),
-            lighting_shininess: settings.get_int("lighting_shininess").unwrap(),
-            // fractal_type: FractalType::A
+            stripe_scale: start.stripe_scale as f32,
+            distance_transition: start.distance_transition as f32,
+            distance_color: start.distance_color,
+            lighting: start.lighting,
+            lighting_direction: start.lighting_direction,
+            lighting_azimuth: start.lighting_azimuth,
+            lighting_opacity: start.lighting_opacity,
+            lighting_ambient: start.lighting_ambient,
+            lighting_diffuse: start.lighting_diffuse,
+            lighting_specular: start.lighting_specular,
+            lighting_shininess: start.lighting_shininess,
+            bookmarks: Arc::new(bookmarks),
+            bookmark_name: String::new(),
+            sequence_directory: "sequence".to_string(),
+            sequence_template: "frame_{frame}".to_string(),
+            sequence_format: SequenceFormat::Png,
+            sequence_remove_centre: false,
+            sequence_frames: start.frames,
+            sequence_frame_offset: start.frame_offset,
+            export_width: start.image_width,
+            export_height: start.image_height,
+            export_supersampling: 2,
+            export_tiled: false,
+            export_progress: None,
+            batch_jobs: Arc::new(Vec::new()),
+            batch_directory: "batch".to_string(),
+            batch_format: SequenceFormat::Png,
+            batch_running: false,
+            batch_running_job: None,
+            fractal_type: FractalType::from_settings(&start),
+            zoom_box_locked: true,
+            preview_enabled: true,
+            notifications: Arc::new(notifications),
+            toast: None,
         })
         .expect("launch failed");
 }
\ No newline at end of file
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use config::{Config, File, Value};
+use druid::{Data, Lens};
+use rust_fractal::util::data_export::DataExport;
+
+use crate::settings::{Settings, toml_string};
+
+// Thumbnails are scaled to fit within this size
+const THUMBNAIL_WIDTH: usize = 96;
+const THUMBNAIL_HEIGHT: usize = 64;
+
+#[derive(Data, Clone, PartialEq, Lens, Debug)]
+pub struct Bookmark {
+    pub name: String,
+    pub real: String,
+    pub imag: String,
+    pub zoom: String,
+    pub iterations: usize,
+    pub rotation: f64,
+    pub fractal_type: String,
+    pub fractal_power: i64,
+    pub period: usize,
+    pub thumbnail_width: usize,
+    pub thumbnail_height: usize,
+    /// RGB pixels of the thumbnail
+    pub thumbnail: Arc<Vec<u8>>,
+}
+
+impl Bookmark {
+    pub fn new(name: String, settings: &Settings, period: usize, data_export: &DataExport) -> Self {
+        let (thumbnail_width, thumbnail_height, thumbnail) = create_thumbnail(data_export);
+
+        Bookmark {
+            name,
+            real: settings.real.clone(),
+            imag: settings.imag.clone(),
+            zoom: settings.zoom.clone(),
+            iterations: settings.iterations,
+            rotation: settings.rotate,
+            fractal_type: settings.fractal_type.clone(),
+            fractal_power: settings.fractal_power,
+            period,
+            thumbnail_width,
+            thumbnail_height,
+            thumbnail: Arc::new(thumbnail),
+        }
+    }
+
+    fn from_table(mut table: HashMap<String, Value>) -> Option<Self> {
+        let mut take = |key: &str| table.remove(key);
+
+        let name = take("name")?.into_str().ok()?;
+        let real = take("real")?.into_str().ok()?;
+        let imag = take("imag")?.into_str().ok()?;
+        let zoom = take("zoom")?.into_str().ok()?;
+        let iterations = take("iterations")?.into_int().ok()? as usize;
+        let rotation = take("rotate").and_then(|value| value.into_float().ok()).unwrap_or(0.0);
+
+        // Bookmarks from before the formula could be chosen are all of the quadratic Mandelbrot set
+        let fractal_type = take("fractal_type").and_then(|value| value.into_str().ok()).unwrap_or_else(|| "mandelbrot".to_string());
+        let fractal_power = take("fractal_power").and_then(|value| value.into_int().ok()).unwrap_or(2);
+
+        let period = take("period").and_then(|value| value.into_int().ok()).unwrap_or(0) as usize;
+
+        let thumbnail_width = take("thumbnail_width").and_then(|value| value.into_int().ok()).unwrap_or(0) as usize;
+        let thumbnail_height = take("thumbnail_height").and_then(|value| value.into_int().ok()).unwrap_or(0) as usize;
+
+        // A missing or damaged thumbnail should not lose the location
+        let thumbnail = take("thumbnail")
+            .and_then(|value| value.into_str().ok())
+            .and_then(|value| decode_hex(&value))
+            .filter(|thumbnail| thumbnail.len() == 3 * thumbnail_width * thumbnail_height);
+
+        let (thumbnail_width, thumbnail_height, thumbnail) = match thumbnail {
+            Some(thumbnail) => (thumbnail_width, thumbnail_height, thumbnail),
+            None => (0, 0, Vec::new()),
+        };
+
+        Some(Bookmark {
+            name,
+            real,
+            imag,
+            zoom,
+            iterations,
+            rotation,
+            fractal_type,
+            fractal_power,
+            period,
+            thumbnail_width,
+            thumbnail_height,
+            thumbnail: Arc::new(thumbnail),
+        })
+    }
+
+    fn to_toml(&self) -> String {
+        format!(
+            "[[bookmarks]]\nname = {}\nreal = \"{}\"\nimag = \"{}\"\nzoom = \"{}\"\niterations = {}\nrotate = {}\nfractal_type = {}\nfractal_power = {}\nperiod = {}\nthumbnail_width = {}\nthumbnail_height = {}\nthumbnail = \"{}\"\n",
+            toml_string(&self.name),
+            self.real,
+            self.imag,
+            self.zoom,
+            self.iterations,
+            self.rotation,
+            toml_string(&self.fractal_type),
+            self.fractal_power,
+            self.period,
+            self.thumbnail_width,
+            self.thumbnail_height,
+            encode_hex(&self.thumbnail))
+    }
+}
+
+/// The bookmarks file, stored in the user's configuration directory so it is shared between working directories.
+pub fn bookmarks_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|path| path.join("rust-fractal-gui").join("bookmarks.toml"))
+}
+
+fn read_bookmarks(path: &Path) -> Result<Vec<Bookmark>, String> {
+    let mut file = Config::default();
+
+    file.merge(File::from(path)).map_err(|e| e.to_string())?;
+
+    Ok(file.get_array("bookmarks").unwrap_or_default()
+        .into_iter()
+        .filter_map(|value| value.into_table().ok())
+        .filter_map(Bookmark::from_table)
+        .collect())
+}
+
+/// The saved bookmarks. A file that cannot be read is moved aside to `bookmarks.toml.bak`, so that
+/// saving new bookmarks does not replace it.
+pub fn load_bookmarks() -> Result<Vec<Bookmark>, String> {
+    let path = match bookmarks_path() {
+        Some(path) if path.exists() => path,
+        _ => return Ok(Vec::new()),
+    };
+
+    read_bookmarks(&path).map_err(|e| {
+        let backup = path.with_extension("toml.bak");
+
+        match std::fs::rename(&path, &backup) {
+            Ok(()) => format!("{} (the file was moved to {})", e, backup.display()),
+            Err(_) => format!("{} (bookmarks will not be saved until the file is fixed or removed)", e),
+        }
+    })
+}
+
+/// Replaces the bookmarks file. An existing file that cannot be read is never replaced, so that the
+/// bookmarks in it are not lost.
+pub fn save_bookmarks(bookmarks: &[Bookmark]) -> Result<(), String> {
+    let path = bookmarks_path().ok_or_else(|| "no configuration directory".to_string())?;
+
+    if path.exists() {
+        read_bookmarks(&path).map_err(|e| format!("{} could not be read, so it was not replaced: {}", path.display(), e))?;
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let output = bookmarks.iter()
+        .map(|bookmark| bookmark.to_toml())
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    // The file is replaced in one step, so a crash while writing leaves the previous one intact
+    let temporary_path = path.with_extension("toml.tmp");
+
+    let write = || -> std::io::Result<()> {
+        let mut file = std::fs::File::create(&temporary_path)?;
+        file.write_all(output.as_bytes())?;
+        file.sync_all()
+    };
+
+    write().map_err(|e| e.to_string())?;
+    std::fs::rename(&temporary_path, &path).map_err(|e| e.to_string())
+}
+
+// Nearest neighbour sample of the current image
+fn create_thumbnail(data_export: &DataExport) -> (usize, usize, Vec<u8>) {
+    let image_width = data_export.image_width;
+    let image_height = data_export.image_height;
+
+    if image_width == 0 || image_height == 0 || data_export.buffer.len() < 3 * image_width * image_height {
+        return (0, 0, Vec::new());
+    }
+
+    let scale = (THUMBNAIL_WIDTH as f64 / image_width as f64).min(THUMBNAIL_HEIGHT as f64 / image_height as f64).min(1.0);
+
+    let width = ((image_width as f64 * scale) as usize).max(1);
+    let height = ((image_height as f64 * scale) as usize).max(1);
+
+    let mut thumbnail = Vec::with_capacity(3 * width * height);
+
+    for j in 0..height {
+        let y = (j * image_height) / height;
+
+        for i in 0..width {
+            let x = (i * image_width) / width;
+            let k = 3 * (y * image_width + x);
+
+            thumbnail.extend_from_slice(&data_export.buffer[k..(k + 3)]);
+        }
+    }
+
+    (width, height, thumbnail)
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(2 * bytes.len());
+
+    for byte in bytes {
+        write!(output, "{:02x}", byte).unwrap();
+    }
+
+    output
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if value.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..value.len()).step_by(2)
+        .map(|i| value.get(i..(i + 2)).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
@@ -1,7 +1,18 @@
-use druid::Selector;
+use std::path::PathBuf;
+
+use druid::{Selector, FileInfo};
 
 use rust_fractal::util::{ComplexExtended, FloatExtended, data_export::ColoringType};
 
+use crate::progress::RenderProgress;
+use crate::bookmarks::Bookmark;
+use crate::palette::{Palette, PaletteStop};
+use crate::export::ExportOptions;
+use crate::notifications::Notification;
+use crate::ui::FractalType;
+use crate::preview::RenderPreview;
+use crate::settings::Settings;
+
 pub const RESET_RENDERER_FAST: Selector<()> = Selector::new("reset_renderer_fast");
 pub const RESET_RENDERER_FULL: Selector<()> = Selector::new("reset_renderer_full");
 
@@ -12,6 +23,10 @@ pub const OPEN_LOCATION: Selector<()> = Selector::new("open_location");
 pub const SAVE_LOCATION: Selector<()> = Selector::new("save_location");
 pub const SAVE_ALL: Selector<()> = Selector::new("save_all");
 pub const SAVE_IMAGE: Selector<()> = Selector::new("save_image");
+pub const EXPORT_ITERATION_MAP: Selector<()> = Selector::new("export_iteration_map");
+pub const SAVE_ITERATION_MAP: Selector<FileInfo> = Selector::new("save_iteration_map");
+pub const IMPORT_ITERATION_MAP: Selector<()> = Selector::new("import_iteration_map");
+pub const OPEN_ITERATION_MAP: Selector<FileInfo> = Selector::new("open_iteration_map");
 
 pub const NATIVE_SIZE: Selector<()> = Selector::new("native_image_size");
 pub const MULTIPLY_SIZE: Selector<f64> = Selector::new("multiply_image_size");
@@ -25,31 +40,111 @@ pub const SET_LOCATION: Selector<()> = Selector::new("set_location");
 
 pub const REVERT_LOCATION: Selector<()> = Selector::new("revert_location");
 
+pub const HISTORY_BACK: Selector<()> = Selector::new("history_back");
+pub const HISTORY_FORWARD: Selector<()> = Selector::new("history_forward");
+
+pub const ADD_BOOKMARK: Selector<()> = Selector::new("add_bookmark");
+pub const GO_TO_BOOKMARK: Selector<Bookmark> = Selector::new("go_to_bookmark");
+pub const DELETE_BOOKMARK: Selector<Bookmark> = Selector::new("delete_bookmark");
+
 pub const SET_OFFSET_SPAN: Selector<()> = Selector::new("set_offset_division");
 
 pub const SET_PERIOD: Selector<usize> = Selector::new("set_period");
-pub const ROOT_FINDING_COMPLETE: Selector<Option<FloatExtended>> = Selector::new("root_finding_complete");
+pub const ROOT_FINDING_COMPLETE: Selector<(usize, Option<FoundRoot>)> = Selector::new("root_finding_complete");
 
 pub const SET_ADVANCED_OPTIONS: Selector<()> = Selector::new("set_advanced_options");
 
 pub const UPDATE_PALETTE: Selector<()> = Selector::new("update_palette");
+
+pub const OPEN_PALETTE_EDITOR: Selector<()> = Selector::new("open_palette_editor");
+pub const PREVIEW_PALETTE: Selector<()> = Selector::new("preview_palette");
+pub const ADD_PALETTE_STOP: Selector<()> = Selector::new("add_palette_stop");
+pub const DELETE_PALETTE_STOP: Selector<PaletteStop> = Selector::new("delete_palette_stop");
+pub const SAVE_PALETTE: Selector<FileInfo> = Selector::new("save_palette");
+pub const LOAD_PALETTE: Selector<()> = Selector::new("load_palette");
+pub const OPEN_PALETTE_FILE: Selector<FileInfo> = Selector::new("open_palette_file");
+pub const NOTIFY: Selector<Notification> = Selector::new("notify");
+pub const DISMISS_NOTIFICATION: Selector<()> = Selector::new("dismiss_notification");
+pub const CLEAR_NOTIFICATIONS: Selector<()> = Selector::new("clear_notifications");
+
 pub const UPDATE_PIXEL_INFORMATION: Selector<()> = Selector::new("update_pixel_information");
 
 pub const STOP_RENDERING: Selector<()> = Selector::new("stop_rendering");
 pub const STOP_ROOT_FINDING: Selector<()> = Selector::new("step_root_finding");
 
-pub const REPAINT: Selector<()> = Selector::new("repaint");
+pub const REPAINT: Selector<usize> = Selector::new("repaint");
 pub const RESET_DEFAULT_LOCATION: Selector<()> = Selector::new("reset_default_location");
 
-pub const UPDATE_RENDERING_PROGRESS: Selector<(usize, f64, usize, usize, usize, usize)> = Selector::new("update_rendering_progress");
-pub const UPDATE_ROOT_PROGRESS: Selector<(usize, usize, ComplexExtended)> = Selector::new("update_root_progress");
+pub const UPDATE_RENDERING_PROGRESS: Selector<RenderProgress> = Selector::new("update_rendering_progress");
+pub const UPDATE_ROOT_PROGRESS: Selector<(usize, usize, usize, ComplexExtended)> = Selector::new("update_root_progress");
 
 pub const ZOOM_OUT: Selector<()> = Selector::new("start_zoom_out");
 pub const ZOOM_OUT_OPTIMISED: Selector<()> = Selector::new("start_zoom_out_optimised");
+pub const ZOOM_OUT_COMPLETE: Selector<()> = Selector::new("zoom_out_complete");
+
+pub const OPEN_SEQUENCE_WINDOW: Selector<()> = Selector::new("open_sequence_window");
+pub const START_SEQUENCE: Selector<()> = Selector::new("start_sequence");
+pub const OPEN_SEQUENCE_MANIFEST: Selector<()> = Selector::new("open_sequence_manifest");
+pub const RESUME_SEQUENCE: Selector<FileInfo> = Selector::new("resume_sequence");
+pub const SEQUENCE_FRAME_SAVED: Selector<usize> = Selector::new("sequence_frame_saved");
+pub const SELECT_SEQUENCE_DIRECTORY: Selector<FileInfo> = Selector::new("select_sequence_directory");
+
+pub const OPEN_EXPORT_WINDOW: Selector<()> = Selector::new("open_export_window");
+pub const START_EXPORT: Selector<()> = Selector::new("start_export");
+pub const SAVE_EXPORT: Selector<FileInfo> = Selector::new("save_export");
+pub const STOP_EXPORT: Selector<()> = Selector::new("stop_export");
+pub const EXPORT_PROGRESS: Selector<Option<(usize, usize)>> = Selector::new("export_progress");
+
+pub const OPEN_BATCH_WINDOW: Selector<()> = Selector::new("open_batch_window");
+pub const SELECT_BATCH_DIRECTORY: Selector<FileInfo> = Selector::new("select_batch_directory");
+pub const ADD_BATCH_PATH: Selector<FileInfo> = Selector::new("add_batch_path");
+pub const START_BATCH: Selector<()> = Selector::new("start_batch");
+pub const STOP_BATCH: Selector<()> = Selector::new("stop_batch");
+pub const CLEAR_BATCH: Selector<()> = Selector::new("clear_batch");
+pub const MOVE_BATCH_JOB_UP: Selector<usize> = Selector::new("move_batch_job_up");
+pub const MOVE_BATCH_JOB_DOWN: Selector<usize> = Selector::new("move_batch_job_down");
+pub const CANCEL_BATCH_JOB: Selector<usize> = Selector::new("cancel_batch_job");
+pub const BATCH_PROGRESS: Selector<(usize, RenderProgress)> = Selector::new("batch_progress");
+pub const BATCH_JOB_COMPLETE: Selector<(usize, Result<(), String>)> = Selector::new("batch_job_complete");
 
 pub const CALCULATE_ROOT: Selector<()> = Selector::new("calculate_root");
-
-pub const THREAD_RESET_RENDERER_FULL: usize = 1;
-pub const THREAD_RESET_RENDERER_FAST: usize = 2;
-pub const THREAD_CALCULATE_ROOT: usize = 3;
+pub const PREVIEW_READY: Selector<RenderPreview> = Selector::new("preview_ready");
+pub const SET_FRACTAL_TYPE: Selector<FractalType> = Selector::new("set_fractal_type");
+
+/// Messages sent to the render thread. Each render carries a job ID which is sent back with the progress
+/// and repaint commands, so that updates from a cancelled render can be ignored.
+pub enum ThreadCommand {
+    /// Regenerates the renderer from a snapshot of the settings and renders a new reference. If a preview is
+    /// requested, a low resolution image of the new location is sent first.
+    ResetRendererFull { job: usize, settings: Settings, frame: Option<(usize, PathBuf)>, preview: bool },
+    /// Renders using the existing reference at the size and iterations of a snapshot of the settings. If a frame
+    /// number and path are given the image is saved there once complete.
+    ResetRendererFast { job: usize, settings: Settings, frame: Option<(usize, PathBuf)> },
+    /// Finds the root in the period finding box of the renderer. The location found replaces the one in the
+    /// snapshot of the settings.
+    CalculateRoot { job: usize, settings: Settings },
+}
+
+/// The location of a root, sent back with the job of the root finding that found it.
+pub struct FoundRoot {
+    pub real: String,
+    pub imag: String,
+    pub zoom: String,
+    pub root_zoom: FloatExtended,
+}
+
+/// An export sent to the export thread. The location in the settings is rendered with a separate renderer
+/// and saved, so the render thread and the preview are not changed.
+pub struct ExportJob {
+    pub settings: Settings,
+    pub palette: Palette,
+    pub options: ExportOptions,
+}
+
+/// A location file from the batch queue, sent to the batch thread to be rendered and saved to the output path.
+pub struct BatchRender {
+    pub id: usize,
+    pub location: PathBuf,
+    pub output: PathBuf,
+}
 
@@ -1,4 +1,6 @@
-use crate::{widgets::FractalData, commands::{UPDATE_PALETTE, UPDATE_PIXEL_INFORMATION}};
+use crate::{widgets::FractalData, commands::*, pixel::LOUPE_SIZE, notifications::Notification};
+
+use druid::{Command, Target};
 
 use druid::piet::{ImageFormat, ImageBuf};
 use druid::widget::prelude::*;
@@ -46,10 +48,86 @@ impl Controller<FractalData, Image> for PixelInformationUpdateController {
     ) {
         match event {
             Event::Command(command) if command.is(UPDATE_PIXEL_INFORMATION) => {
-                // child.set_image_data(ImageBuf::from_raw(data.pixel_rgb.lock().as_ref(), ImageFormat::Rgb, 15, 15));
+                let raw_buffer = data.pixel_rgb.lock().clone();
+
+                child.set_image_data(ImageBuf::from_raw(raw_buffer, ImageFormat::Rgb, LOUPE_SIZE, LOUPE_SIZE));
                 ctx.request_paint();
             }
             other => child.event(ctx, other, data, env),
         }
     }
+}
+
+pub struct FractalTypeController;
+
+impl<W: Widget<FractalData>> Controller<FractalData, W> for FractalTypeController {
+    fn update(&mut self, child: &mut W, ctx: &mut UpdateCtx, old_data: &FractalData, data: &FractalData, env: &Env) {
+        // The renderer is regenerated whenever a different formula is chosen
+        if old_data.fractal_type != data.fractal_type {
+            ctx.submit_command(SET_FRACTAL_TYPE.with(data.fractal_type));
+        }
+
+        child.update(ctx, old_data, data, env)
+    }
+}
+
+pub struct SequenceDirectoryController;
+
+impl<W: Widget<FractalData>> Controller<FractalData, W> for SequenceDirectoryController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut FractalData,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(command) if command.is(SELECT_SEQUENCE_DIRECTORY) => {
+                let file_info = command.get_unchecked(SELECT_SEQUENCE_DIRECTORY);
+
+                data.sequence_directory = file_info.path().to_string_lossy().to_string();
+            }
+            other => child.event(ctx, other, data, env),
+        }
+    }
+}
+
+pub struct PaletteEditorController;
+
+impl<W: Widget<FractalData>> Controller<FractalData, W> for PaletteEditorController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut FractalData,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(command) if command.is(ADD_PALETTE_STOP) => {
+                data.palette.add_stop();
+            }
+            Event::Command(command) if command.is(DELETE_PALETTE_STOP) => {
+                data.palette.remove_stop(command.get_unchecked(DELETE_PALETTE_STOP));
+            }
+            Event::Command(command) if command.is(SAVE_PALETTE) => {
+                let file_info = command.get_unchecked(SAVE_PALETTE);
+
+                if let Err(e) = data.palette.save(file_info.path(), data.palette_iteration_span, data.palette_offset) {
+                    ctx.submit_command(Command::new(NOTIFY, Notification::error(format!("Error writing {}: {}", file_info.path().display(), e)), Target::Global));
+                }
+            }
+            other => child.event(ctx, other, data, env),
+        }
+    }
+
+    fn update(&mut self, child: &mut W, ctx: &mut UpdateCtx, old_data: &FractalData, data: &FractalData, env: &Env) {
+        // Preview every change to the palette on the main window
+        if !old_data.palette.same(&data.palette) {
+            ctx.submit_command(Command::new(PREVIEW_PALETTE, (), Target::Global));
+        }
+
+        child.update(ctx, old_data, data, env)
+    }
 }
\ No newline at end of file
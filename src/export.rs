@@ -0,0 +1,332 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rust_fractal::renderer::FractalRenderer;
+use rust_fractal::util::{ComplexFixed, FloatArbitrary, FloatExtended, get_delta_top_left, string_to_extended, extended_to_string_long};
+
+use crate::formats::{TextChunk, PngStreamWriter, apply_palette, is_png_file, location_text_chunks, write_png_text};
+use crate::palette::Palette;
+use crate::pixel::location_arbitrary;
+use crate::settings::Settings;
+
+// The factors offered in the export window, along with no supersampling
+pub const SUPERSAMPLING_FACTORS: [usize; 6] = [1, 2, 3, 4, 6, 8];
+
+// The largest size of each tile of a tiled export, in output pixels
+const TILE_SIZE: usize = 1024;
+
+/// The size and quality of an image rendered separately from the preview.
+#[derive(Clone, Debug)]
+pub struct ExportOptions {
+    pub width: usize,
+    pub height: usize,
+    /// Each output pixel is the average of this many pixels in each direction
+    pub supersampling: usize,
+    /// Renders the image in tiles and writes it a few rows at a time, for images too large to fit in memory
+    pub tiled: bool,
+    pub path: PathBuf,
+}
+
+// The 8 bit output of the renderer is sRGB, so pixels are averaged after converting to linear values
+pub fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> u8 {
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Reduces an RGB image by an integer factor, where each output pixel is the average of the block it covers.
+pub fn downsample(buffer: &[u8], width: usize, height: usize, factor: usize) -> Vec<u8> {
+    if factor <= 1 {
+        return buffer.to_vec();
+    }
+
+    let output_width = width / factor;
+    let output_height = height / factor;
+
+    let linear = (0..=255).map(srgb_to_linear).collect::<Vec<f32>>();
+    let weight = 1.0 / (factor * factor) as f32;
+
+    let mut output = Vec::with_capacity(3 * output_width * output_height);
+
+    for j in 0..output_height {
+        for i in 0..output_width {
+            let mut sum = [0.0f32; 3];
+
+            for y in (j * factor)..((j + 1) * factor) {
+                for x in (i * factor)..((i + 1) * factor) {
+                    let k = 3 * (y * width + x);
+
+                    for channel in 0..3 {
+                        sum[channel] += linear[buffer[k + channel] as usize];
+                    }
+                }
+            }
+
+            output.extend(sum.iter().map(|value| linear_to_srgb(value * weight)));
+        }
+    }
+
+    output
+}
+
+/// Renders the location in the settings at the size of the export with a new renderer, so that
+/// the preview is not changed, and saves the downsampled image. The progress is given as the
+/// number of tiles completed out of the total.
+pub fn render_export(settings: &Settings, palette: &Palette, options: &ExportOptions, stop_flag: Arc<AtomicBool>, progress: impl Fn(usize, usize)) -> Result<(), String> {
+    if options.width == 0 || options.height == 0 {
+        return Err("the image size must be at least one pixel".to_string());
+    }
+
+    let mut export_settings = settings.clone();
+
+    // The export does not need to be shown as it renders
+    export_settings.show_output = false;
+
+    // The location is stored at the exported size so that it can be opened again
+    let mut location_settings = settings.clone();
+
+    location_settings.image_width = options.width;
+    location_settings.image_height = options.height;
+
+    if options.tiled {
+        return render_tiled(&export_settings, palette, options, &location_text_chunks(&location_settings, palette), stop_flag, progress);
+    }
+
+    let output = options.path.to_str().ok_or_else(|| format!("invalid path {}", options.path.display()))?;
+
+    let factor = options.supersampling.max(1);
+
+    export_settings.image_width = options.width * factor;
+    export_settings.image_height = options.height * factor;
+
+    progress(0, 1);
+
+    let mut renderer = FractalRenderer::new(export_settings.to_config());
+
+    apply_palette(&mut renderer.data_export.lock(), &export_settings, palette);
+
+    renderer.render_frame(0, String::from(""), stop_flag.clone());
+
+    if stop_flag.load(Ordering::SeqCst) {
+        return Err("the export was stopped".to_string());
+    }
+
+    let mut data_export = renderer.data_export.lock();
+
+    data_export.buffer = downsample(&data_export.buffer, options.width * factor, options.height * factor, factor);
+    data_export.image_width = options.width;
+    data_export.image_height = options.height;
+
+    data_export.save_colour(output);
+
+    if is_png_file(&options.path) {
+        write_png_text(&options.path, &location_text_chunks(&location_settings, palette))?;
+    }
+
+    progress(1, 1);
+
+    Ok(())
+}
+
+// Splits a length into parts of at most the tile size, with the remainder spread evenly between them
+fn tile_bounds(length: usize) -> Vec<(usize, usize)> {
+    let count = (length + TILE_SIZE - 1) / TILE_SIZE;
+
+    (0..count)
+        .map(|index| (index * length / count, (index + 1) * length / count))
+        .collect()
+}
+
+/// The location of a pixel in an image, at a precision high enough for the zoom.
+fn offset_location(settings: &Settings, width: usize, height: usize, i: f64, j: f64) -> Result<(String, String), String> {
+    let zoom = string_to_extended(&settings.zoom);
+    let rotate = settings.rotate.to_radians();
+
+    let (mut location_real, mut location_imag) = location_arbitrary(settings)?;
+    let precision = location_real.prec();
+
+    let cos_rotate = rotate.cos();
+    let sin_rotate = rotate.sin();
+
+    let delta_pixel = 4.0 / ((height - 1) as f64 * zoom.mantissa);
+    let delta_top_left = get_delta_top_left(delta_pixel, width, height, cos_rotate, sin_rotate);
+
+    let element = ComplexFixed::new(
+        i * delta_pixel * cos_rotate - j * delta_pixel * sin_rotate + delta_top_left.re,
+        i * delta_pixel * sin_rotate + j * delta_pixel * cos_rotate + delta_top_left.im
+    );
+
+    let scale = FloatArbitrary::with_val(precision, -zoom.exponent).exp2();
+
+    location_real += FloatArbitrary::with_val(precision, element.re) * &scale;
+    location_imag += FloatArbitrary::with_val(precision, element.im) * &scale;
+
+    Ok((location_real.to_string(), location_imag.to_string()))
+}
+
+/// Renders part of an image as a separate render centred on the part, with the zoom adjusted so that
+/// the pixel spacing matches the full image. The part is given in the pixels of the full image as the
+/// left, top, width and height, and the RGB pixels of the part are returned.
+fn render_tile(settings: &Settings, palette: &Palette, full_width: usize, full_height: usize, tile: (usize, usize, usize, usize), stop_flag: Arc<AtomicBool>) -> Result<Vec<u8>, String> {
+    let (left, top, tile_width, tile_height) = tile;
+
+    if full_height < 2 || tile_height < 2 {
+        return Err("the image and each tile must be at least two pixels high".to_string());
+    }
+
+    // The centre of the tile in the pixels of the full image
+    let (real, imag) = offset_location(
+        settings,
+        full_width,
+        full_height,
+        left as f64 + 0.5 * (tile_width - 1) as f64,
+        top as f64 + 0.5 * (tile_height - 1) as f64)?;
+
+    // The pixel spacing is inversely proportional to the zoom and to one less than the image height
+    let zoom = string_to_extended(&settings.zoom);
+
+    let mut tile_zoom = FloatExtended::new(zoom.mantissa * (full_height - 1) as f64 / (tile_height - 1) as f64, zoom.exponent);
+    tile_zoom.reduce();
+
+    let mut tile_settings = settings.clone();
+
+    tile_settings.real = real;
+    tile_settings.imag = imag;
+    tile_settings.zoom = extended_to_string_long(tile_zoom);
+    tile_settings.image_width = tile_width;
+    tile_settings.image_height = tile_height;
+
+    // Every tile must use the same iterations so that the colouring matches
+    tile_settings.auto_adjust_iterations = false;
+    tile_settings.remove_centre = false;
+
+    let mut renderer = FractalRenderer::new(tile_settings.to_config());
+
+    apply_palette(&mut renderer.data_export.lock(), &tile_settings, palette);
+
+    renderer.render_frame(0, String::from(""), stop_flag.clone());
+
+    if stop_flag.load(Ordering::SeqCst) {
+        return Err("the export was stopped".to_string());
+    }
+
+    let buffer = renderer.data_export.lock().buffer.clone();
+
+    Ok(buffer)
+}
+
+/// Renders the image one tile at a time and writes each row of tiles to the file as it is completed,
+/// so that only one row of tiles is held in memory.
+fn render_tiled(settings: &Settings, palette: &Palette, options: &ExportOptions, text_chunks: &[TextChunk], stop_flag: Arc<AtomicBool>, progress: impl Fn(usize, usize)) -> Result<(), String> {
+    if !is_png_file(&options.path) {
+        return Err("tiled exports can only be saved as PNG".to_string());
+    }
+
+    let factor = options.supersampling.max(1);
+
+    let full_width = options.width * factor;
+    let full_height = options.height * factor;
+
+    let columns = tile_bounds(options.width);
+    let rows = tile_bounds(options.height);
+
+    let total = columns.len() * rows.len();
+    let mut completed = 0;
+
+    progress(completed, total);
+
+    let mut writer = PngStreamWriter::new(&options.path, options.width, options.height, text_chunks)?;
+
+    for (top, bottom) in &rows {
+        let row_height = bottom - top;
+        let mut row_buffer = vec![0u8; 3 * options.width * row_height];
+
+        for (left, right) in &columns {
+            if stop_flag.load(Ordering::SeqCst) {
+                return Err("the export was stopped".to_string());
+            }
+
+            let tile_width = (right - left) * factor;
+            let tile_height = row_height * factor;
+
+            let tile = render_tile(settings, palette, full_width, full_height, (left * factor, top * factor, tile_width, tile_height), stop_flag.clone())?;
+            let tile = downsample(&tile, tile_width, tile_height, factor);
+            let output_width = right - left;
+
+            for (j, tile_row) in tile.chunks_exact(3 * output_width).enumerate() {
+                let start = 3 * (j * options.width + left);
+                row_buffer[start..(start + 3 * output_width)].copy_from_slice(tile_row);
+            }
+
+            completed += 1;
+            progress(completed, total);
+        }
+
+        writer.write_rows(&row_buffer)?;
+    }
+
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A tile must line up with the same pixels of an untiled render of the whole image
+    #[test]
+    fn tile_matches_crop_of_full_render() {
+        let mut settings = Settings::default();
+
+        settings.real = "-0.7436".to_string();
+        settings.imag = "0.1318".to_string();
+        settings.zoom = "2E2".to_string();
+        settings.iterations = 500;
+        settings.image_width = 96;
+        settings.image_height = 64;
+        settings.auto_adjust_iterations = false;
+        settings.remove_centre = false;
+        settings.jitter = false;
+        settings.show_output = false;
+
+        let stop_flag = Arc::new(AtomicBool::new(false));
+
+        let mut renderer = FractalRenderer::new(settings.to_config());
+        renderer.render_frame(0, String::from(""), stop_flag.clone());
+
+        let palette = Palette::from_data_export(&renderer.data_export.lock());
+        let full = renderer.data_export.lock().buffer.clone();
+
+        let (left, top, tile_width, tile_height) = (40, 16, 32, 24);
+        let tile = render_tile(&settings, &palette, 96, 64, (left, top, tile_width, tile_height), stop_flag).unwrap();
+
+        assert_eq!(tile.len(), 3 * tile_width * tile_height);
+
+        // Perturbation with a different reference can change the odd pixel by a small amount
+        let different = (0..tile_height)
+            .flat_map(|j| (0..tile_width).map(move |i| (i, j)))
+            .filter(|(i, j)| {
+                let k_tile = 3 * (j * tile_width + i);
+                let k_full = 3 * ((top + j) * 96 + left + i);
+
+                (0..3).any(|channel| (tile[k_tile + channel] as i32 - full[k_full + channel] as i32).abs() > 8)
+            })
+            .count();
+
+        assert!(different * 100 <= tile_width * tile_height, "{} of {} pixels differ", different, tile_width * tile_height);
+    }
+}
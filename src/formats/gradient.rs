@@ -0,0 +1,317 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::palette::{Palette, PaletteStop, PaletteInterpolation};
+use super::kf;
+
+// The extensions of the palette formats used by other programs
+pub const PALETTE_EXTENSIONS: &[&str] = &["map", "ggr", "kfp", "kfr", "ugr"];
+
+// UltraFractal gradients have this many positions
+const UGR_SIZE: f64 = 400.0;
+
+/// A palette read from another program, with the iteration span and offset if the format defines them.
+pub struct ImportedPalette {
+    pub palette: Palette,
+    pub iteration_span: Option<f64>,
+    pub offset: Option<f64>,
+}
+
+/// Files that only contain a palette. Kalles Fraktaler locations also contain one, but are opened as locations.
+pub fn is_palette_file(path: &Path) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => ["map", "ggr", "kfp", "ugr"].contains(&extension.to_ascii_lowercase().as_ref()),
+        None => false
+    }
+}
+
+pub fn load_palette_file(path: &Path) -> Result<ImportedPalette, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+    let extension = path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_ref() {
+        "map" => parse_map(&text),
+        "ggr" => parse_ggr(&text),
+        "kfp" | "kfr" => {
+            let (palette, iteration_span, offset) = kf::palette_from_fields(&kf::parse_fields(&text))?;
+
+            Ok(ImportedPalette {
+                palette,
+                iteration_span: Some(iteration_span),
+                offset: Some(offset),
+            })
+        },
+        "ugr" => parse_ugr(&text),
+        _ => Err(format!("unknown palette format {}", extension))
+    }
+}
+
+/// Fractint maps have one `r g b` colour per line, and advance one colour per iteration.
+fn parse_map(text: &str) -> Result<ImportedPalette, String> {
+    let colours = text.lines()
+        .filter_map(|line| {
+            let mut values = line.split_whitespace().map(|value| value.parse::<u8>());
+
+            match (values.next(), values.next(), values.next()) {
+                (Some(Ok(r)), Some(Ok(g)), Some(Ok(b))) => Some((r, g, b)),
+                _ => None
+            }
+        })
+        .collect::<Vec<(u8, u8, u8)>>();
+
+    if colours.is_empty() {
+        return Err("no colours found".to_string());
+    }
+
+    Ok(ImportedPalette {
+        iteration_span: Some(colours.len() as f64),
+        offset: Some(0.0),
+        palette: Palette::from_colours(&colours),
+    })
+}
+
+/// GIMP gradients are made of segments with a colour at each end. Transparency and the HSV blending modes are ignored.
+fn parse_ggr(text: &str) -> Result<ImportedPalette, String> {
+    let mut lines = text.lines().map(|line| line.trim()).filter(|line| !line.is_empty());
+
+    if lines.next() != Some("GIMP Gradient") {
+        return Err("missing GIMP Gradient header".to_string());
+    }
+
+    let mut stops: Vec<PaletteStop> = Vec::new();
+    let mut segment_types = Vec::new();
+
+    for line in lines {
+        if line.starts_with("Name:") {
+            continue;
+        }
+
+        let values = line.split_whitespace()
+            .map(|value| value.parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        // The segment count
+        if values.len() == 1 {
+            continue;
+        }
+
+        if values.len() < 11 {
+            return Err(format!("invalid segment: {}", line));
+        }
+
+        let to_colour = |r: f64, g: f64, b: f64| {
+            let channel = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            (channel(r), channel(g), channel(b))
+        };
+
+        let (left, middle, right) = (values[0], values[1], values[2]);
+        let left_colour = to_colour(values[3], values[4], values[5]);
+        let right_colour = to_colour(values[7], values[8], values[9]);
+
+        if stops.last().map(|stop| stop.position != left || stop.colour() != left_colour).unwrap_or(true) {
+            stops.push(PaletteStop::new(left, left_colour));
+        }
+
+        // The middle of the segment is always half way between the two colours
+        if (middle - 0.5 * (left + right)).abs() > 1e-6 {
+            let mix = |a: u8, b: u8| ((a as f64 + b as f64) * 0.5).round() as u8;
+
+            stops.push(PaletteStop::new(middle, (
+                mix(left_colour.0, right_colour.0),
+                mix(left_colour.1, right_colour.1),
+                mix(left_colour.2, right_colour.2))));
+        }
+
+        stops.push(PaletteStop::new(right, right_colour));
+        segment_types.push(values.get(11).copied().unwrap_or(0.0) as i64);
+    }
+
+    if stops.is_empty() {
+        return Err("no segments found".to_string());
+    }
+
+    let interpolation = if segment_types.iter().all(|segment_type| *segment_type == 5) {
+        PaletteInterpolation::Step
+    } else if segment_types.iter().all(|segment_type| *segment_type == 2) {
+        PaletteInterpolation::Smooth
+    } else {
+        PaletteInterpolation::Linear
+    };
+
+    Ok(ImportedPalette {
+        palette: Palette {
+            stops: Arc::new(stops),
+            interpolation,
+        },
+        iteration_span: None,
+        offset: None,
+    })
+}
+
+/// UltraFractal gradient files can contain several gradients, only the first is used. Colours are stored as BGR integers.
+fn parse_ugr(text: &str) -> Result<ImportedPalette, String> {
+    let start = text.find("gradient:").ok_or_else(|| "no gradient section".to_string())?;
+    let section = &text[(start + "gradient:".len())..];
+
+    let end = ["opacity:", "}"].iter()
+        .filter_map(|marker| section.find(marker))
+        .min()
+        .unwrap_or(section.len());
+
+    let mut smooth = false;
+    let mut rotation = 0.0;
+    let mut index = None;
+    let mut nodes = Vec::new();
+
+    for token in section[..end].split_whitespace() {
+        let (key, value) = match token.split_once('=') {
+            Some(pair) => pair,
+            None => continue
+        };
+
+        match key {
+            "smooth" => smooth = value == "yes",
+            "rotation" => rotation = value.parse::<f64>().unwrap_or(0.0),
+            "index" => index = value.parse::<f64>().ok(),
+            "color" => {
+                if let (Some(position), Ok(colour)) = (index.take(), value.parse::<u32>()) {
+                    nodes.push((position, ((colour & 0xff) as u8, ((colour >> 8) & 0xff) as u8, ((colour >> 16) & 0xff) as u8)));
+                }
+            },
+            _ => {}
+        }
+    }
+
+    if nodes.is_empty() {
+        return Err("no colours found".to_string());
+    }
+
+    nodes.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut stops = nodes.iter()
+        .map(|(position, colour)| PaletteStop::new(position / UGR_SIZE, *colour))
+        .collect::<Vec<PaletteStop>>();
+
+    // The gradient wraps around, so the ends take the colour between the last and first nodes
+    let (first_position, first_colour) = nodes[0];
+    let (last_position, last_colour) = nodes[nodes.len() - 1];
+
+    let width = first_position + UGR_SIZE - last_position;
+    let t = if width > 0.0 { (UGR_SIZE - last_position) / width } else { 0.0 };
+    let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+
+    let wrap_colour = (
+        mix(last_colour.0, first_colour.0),
+        mix(last_colour.1, first_colour.1),
+        mix(last_colour.2, first_colour.2));
+
+    if first_position > 0.0 {
+        stops.insert(0, PaletteStop::new(0.0, wrap_colour));
+    }
+
+    if last_position < UGR_SIZE {
+        stops.push(PaletteStop::new(1.0, wrap_colour));
+    }
+
+    Ok(ImportedPalette {
+        palette: Palette {
+            stops: Arc::new(stops),
+            interpolation: if smooth {
+                PaletteInterpolation::Smooth
+            } else {
+                PaletteInterpolation::Linear
+            },
+        },
+        iteration_span: None,
+        offset: Some((rotation / UGR_SIZE).rem_euclid(1.0)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_skips_lines_without_colours() {
+        let imported = parse_map("255 0 0\n0 255 0 green\nnot a colour\n0 0 255\n").unwrap();
+
+        assert_eq!(imported.palette, Palette::from_colours(&[(255, 0, 0), (0, 255, 0), (0, 0, 255)]));
+        assert_eq!(imported.iteration_span, Some(3.0));
+        assert_eq!(imported.offset, Some(0.0));
+
+        assert!(parse_map("no colours here\n").is_err());
+    }
+
+    #[test]
+    fn ggr_joins_segments() {
+        let text = "GIMP Gradient\n\
+            Name: Test\n\
+            2\n\
+            0.0 0.25 0.5 1.0 0.0 0.0 1.0 0.0 0.0 1.0 1.0 0 0\n\
+            0.5 0.75 1.0 0.0 0.0 1.0 1.0 1.0 1.0 1.0 1.0 0 0\n";
+
+        let imported = parse_ggr(text).unwrap();
+
+        // The shared colour at the join is only stored once
+        assert_eq!(*imported.palette.stops, vec![
+            PaletteStop::new(0.0, (255, 0, 0)),
+            PaletteStop::new(0.5, (0, 0, 255)),
+            PaletteStop::new(1.0, (255, 255, 255)),
+        ]);
+
+        assert_eq!(imported.palette.interpolation, PaletteInterpolation::Linear);
+        assert_eq!(imported.iteration_span, None);
+    }
+
+    #[test]
+    fn ggr_keeps_moved_middle() {
+        let text = "GIMP Gradient\n1\n0.0 0.25 1.0 0.0 0.0 0.0 1.0 1.0 1.0 1.0 1.0 5 0\n";
+
+        let imported = parse_ggr(text).unwrap();
+
+        assert_eq!(*imported.palette.stops, vec![
+            PaletteStop::new(0.0, (0, 0, 0)),
+            PaletteStop::new(0.25, (128, 128, 128)),
+            PaletteStop::new(1.0, (255, 255, 255)),
+        ]);
+
+        assert_eq!(imported.palette.interpolation, PaletteInterpolation::Step);
+    }
+
+    #[test]
+    fn ggr_needs_header() {
+        assert!(parse_ggr("0.0 0.5 1.0 0.0 0.0 0.0 1.0 1.0 1.0 1.0 1.0 0 0\n").is_err());
+        assert!(parse_ggr("GIMP Gradient\n0.0 0.5 1.0\n").is_err());
+    }
+
+    #[test]
+    fn ugr_wraps_ends() {
+        let text = "test {\n\
+            gradient:\n\
+              title=\"test\" smooth=yes rotation=100\n\
+              index=0 color=255\n\
+              index=200 color=16711680\n\
+            opacity:\n\
+              smooth=no index=0 opacity=255\n\
+            }\n";
+
+        let imported = parse_ugr(text).unwrap();
+
+        // Colours are BGR, and the end takes the colour half way back to the first node
+        assert_eq!(*imported.palette.stops, vec![
+            PaletteStop::new(0.0, (255, 0, 0)),
+            PaletteStop::new(0.5, (0, 0, 255)),
+            PaletteStop::new(1.0, (255, 0, 0)),
+        ]);
+
+        assert_eq!(imported.palette.interpolation, PaletteInterpolation::Smooth);
+        assert_eq!(imported.offset, Some(0.25));
+
+        assert!(parse_ugr("test { }").is_err());
+    }
+}
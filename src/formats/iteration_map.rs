@@ -0,0 +1,376 @@
+//! Iteration maps store the per-pixel data of a finished render so that it can be recoloured later without
+//! running the renderer again, in the same way as the `.kfb` files of Kalles Fraktaler.
+//!
+//! All values are little endian, and the pixel arrays are in row-major order starting at the top left:
+//!
+//! | Field      | Type           | Notes                                                      |
+//! |------------|----------------|------------------------------------------------------------|
+//! | magic      | `[u8; 4]`      | `RFIM`                                                     |
+//! | version    | `u32`          | currently 1                                                |
+//! | width      | `u32`          |                                                            |
+//! | height     | `u32`          |                                                            |
+//! | flags      | `u32`          | bit 0: distance, bit 1: stripe, bit 2: glitch flags stored |
+//! | length     | `u32`          | length of the location in bytes                            |
+//! | location   | `[u8; length]` | the full configuration as UTF-8 TOML                       |
+//! | iterations | `[u32; n]`     | `n = width * height`                                       |
+//! | smooth     | `[f32; n]`     |                                                            |
+//! | distance   | `[f32; n]`     | only if flag bit 0 is set                                  |
+//! | stripe     | `[f32; n]`     | only if flag bit 1 is set                                  |
+//! | glitched   | `[u8; n]`      | only if flag bit 2 is set, 1 for glitched pixels           |
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use rust_fractal::util::data_export::{DataExport, DataType};
+
+pub const ITERATION_MAP_EXTENSION: &str = "rfm";
+
+const MAGIC: &[u8; 4] = b"RFIM";
+const VERSION: u32 = 1;
+
+const FLAG_DISTANCE: u32 = 1;
+const FLAG_STRIPE: u32 = 2;
+const FLAG_GLITCHED: u32 = 4;
+
+/// The contents of an iteration map. The optional values are empty if they were not stored.
+pub struct IterationMap {
+    pub width: usize,
+    pub height: usize,
+    pub location: String,
+    pub iterations: Vec<u32>,
+    pub smooth: Vec<f32>,
+    pub distance: Vec<f32>,
+    pub stripe: Vec<f32>,
+    pub glitched: Vec<bool>,
+}
+
+impl IterationMap {
+    /// The data type of the render that the stored values came from.
+    pub fn data_type(&self) -> DataType {
+        match (self.distance.is_empty(), self.stripe.is_empty()) {
+            (false, false) => DataType::DistanceStripe,
+            (false, true) => DataType::Distance,
+            (true, false) => DataType::Stripe,
+            (true, true) => DataType::Iteration
+        }
+    }
+
+    /// Replaces the pixel data of the renderer output. The colours still need to be regenerated.
+    pub fn fill(self, data_export: &mut DataExport) {
+        data_export.data_type = self.data_type();
+        data_export.image_width = self.width;
+        data_export.image_height = self.height;
+        data_export.buffer = vec![0u8; 3 * self.width * self.height];
+        data_export.iterations = self.iterations;
+        data_export.smooth = self.smooth;
+        data_export.distance = self.distance;
+        data_export.stripe = self.stripe;
+        data_export.glitched = self.glitched;
+    }
+}
+
+pub fn is_iteration_map_file(path: &Path) -> bool {
+    matches!(path.extension().and_then(|extension| extension.to_str()), Some(extension) if extension.eq_ignore_ascii_case(ITERATION_MAP_EXTENSION))
+}
+
+/// Writes the pixel data of a finished render along with its location.
+pub fn save_iteration_map(path: &Path, data_export: &DataExport, location: &str) -> Result<(), String> {
+    let n = data_export.image_width * data_export.image_height;
+
+    if n == 0 || data_export.iterations.len() != n || data_export.smooth.len() != n {
+        return Err("there is no complete render to export".to_string());
+    }
+
+    // Values are only stored by the coloring methods that use them
+    let mut flags = 0;
+
+    if data_export.distance.len() == n {
+        flags |= FLAG_DISTANCE;
+    }
+
+    if data_export.stripe.len() == n {
+        flags |= FLAG_STRIPE;
+    }
+
+    if data_export.glitched.len() == n {
+        flags |= FLAG_GLITCHED;
+    }
+
+    let write = || -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(MAGIC)?;
+
+        for value in [VERSION, data_export.image_width as u32, data_export.image_height as u32, flags, location.len() as u32] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+
+        writer.write_all(location.as_bytes())?;
+
+        for value in &data_export.iterations {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+
+        for value in &data_export.smooth {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+
+        if flags & FLAG_DISTANCE != 0 {
+            for value in &data_export.distance {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+
+        if flags & FLAG_STRIPE != 0 {
+            for value in &data_export.stripe {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+
+        if flags & FLAG_GLITCHED != 0 {
+            let glitched = data_export.glitched.iter().map(|value| *value as u8).collect::<Vec<u8>>();
+            writer.write_all(&glitched)?;
+        }
+
+        writer.flush()
+    };
+
+    write().map_err(|e| e.to_string())
+}
+
+// Reads consecutive values from the file, failing if it ends early
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let output = self.position.checked_add(length)
+            .and_then(|end| self.bytes.get(self.position..end))
+            .ok_or_else(|| "unexpected end of file".to_string())?;
+
+        self.position += length;
+
+        Ok(output)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u32_array(&mut self, n: usize) -> Result<Vec<u32>, String> {
+        Ok(self.take(4 * n)?.chunks_exact(4).map(|value| u32::from_le_bytes(value.try_into().unwrap())).collect())
+    }
+
+    fn f32_array(&mut self, n: usize) -> Result<Vec<f32>, String> {
+        Ok(self.take(4 * n)?.chunks_exact(4).map(|value| f32::from_le_bytes(value.try_into().unwrap())).collect())
+    }
+}
+
+pub fn load_iteration_map(path: &Path) -> Result<IterationMap, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+
+    let mut reader = Reader {
+        bytes: &bytes,
+        position: 0,
+    };
+
+    if reader.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+        return Err("not an iteration map".to_string());
+    }
+
+    let version = reader.u32()?;
+
+    if version > VERSION {
+        return Err(format!("iteration map version {} is newer than this program supports", version));
+    }
+
+    let width = reader.u32()? as usize;
+    let height = reader.u32()? as usize;
+    let flags = reader.u32()?;
+    let length = reader.u32()? as usize;
+
+    let location = String::from_utf8(reader.take(length)?.to_vec()).map_err(|e| e.to_string())?;
+
+    let n = width.checked_mul(height).ok_or_else(|| format!("an iteration map cannot be {}x{} pixels", width, height))?;
+
+    if n == 0 {
+        return Err("the iteration map is empty".to_string());
+    }
+
+    // The iterations and smooth values, then each of the optional arrays given by the flags
+    let mut pixel_size = 8;
+
+    if flags & FLAG_DISTANCE != 0 {
+        pixel_size += 4;
+    }
+
+    if flags & FLAG_STRIPE != 0 {
+        pixel_size += 4;
+    }
+
+    if flags & FLAG_GLITCHED != 0 {
+        pixel_size += 1;
+    }
+
+    // The header is checked against the size of the file before anything is allocated
+    let remaining = bytes.len() - reader.position;
+
+    if n.checked_mul(pixel_size) != Some(remaining) {
+        return Err(format!("the file should contain {}x{} pixels of {} bytes, but {} bytes remain", width, height, pixel_size, remaining));
+    }
+
+    let iterations = reader.u32_array(n)?;
+    let smooth = reader.f32_array(n)?;
+
+    let distance = if flags & FLAG_DISTANCE != 0 {
+        reader.f32_array(n)?
+    } else {
+        Vec::new()
+    };
+
+    let stripe = if flags & FLAG_STRIPE != 0 {
+        reader.f32_array(n)?
+    } else {
+        Vec::new()
+    };
+
+    let glitched = if flags & FLAG_GLITCHED != 0 {
+        reader.take(n)?.iter().map(|value| *value != 0).collect()
+    } else {
+        Vec::new()
+    };
+
+    Ok(IterationMap {
+        width,
+        height,
+        location,
+        iterations,
+        smooth,
+        distance,
+        stripe,
+        glitched,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+    use std::path::PathBuf;
+
+    use rust_fractal::renderer::FractalRenderer;
+
+    use super::*;
+    use crate::settings::Settings;
+
+    // Each test uses its own file, as tests run in parallel
+    fn temporary_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rust_fractal_gui_{}_{}.{}", name, std::process::id(), ITERATION_MAP_EXTENSION))
+    }
+
+    fn header(width: u32, height: u32, flags: u32, location: &str) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+
+        for value in [VERSION, width, height, flags, location.len() as u32] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(location.as_bytes());
+        bytes
+    }
+
+    fn bits(values: &[f32]) -> Vec<u32> {
+        values.iter().map(|value| value.to_bits()).collect()
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut settings = Settings::default();
+
+        settings.image_width = 24;
+        settings.image_height = 16;
+        settings.iterations = 200;
+        settings.coloring_type = "distance_stripe".to_string();
+        settings.auto_adjust_iterations = false;
+        settings.remove_centre = false;
+        settings.jitter = false;
+        settings.show_output = false;
+
+        let mut renderer = FractalRenderer::new(settings.to_config());
+        renderer.render_frame(0, String::from(""), Arc::new(AtomicBool::new(false)));
+
+        let data_export = renderer.data_export.lock();
+        let location = "real = \"-0.75\"\nimag = \"0.1\"\n";
+
+        let path = temporary_path("save_and_load");
+
+        save_iteration_map(&path, &data_export, location).unwrap();
+        let loaded = load_iteration_map(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let iteration_map = loaded.unwrap();
+
+        assert_eq!((iteration_map.width, iteration_map.height), (24, 16));
+        assert_eq!(iteration_map.location, location);
+        assert_eq!(iteration_map.iterations, data_export.iterations);
+        assert_eq!(bits(&iteration_map.smooth), bits(&data_export.smooth));
+        assert_eq!(bits(&iteration_map.distance), bits(&data_export.distance));
+        assert_eq!(bits(&iteration_map.stripe), bits(&data_export.stripe));
+        assert_eq!(iteration_map.glitched, data_export.glitched);
+    }
+
+    #[test]
+    fn load_optional_values() {
+        let mut bytes = header(2, 1, FLAG_STRIPE | FLAG_GLITCHED, "");
+
+        for value in [5u32, 7] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        for value in [0.25f32, 0.5, 1.5, 2.5] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&[0, 1]);
+
+        let path = temporary_path("load_optional_values");
+
+        std::fs::write(&path, &bytes).unwrap();
+        let loaded = load_iteration_map(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let iteration_map = loaded.unwrap();
+
+        assert_eq!(iteration_map.iterations, vec![5, 7]);
+        assert_eq!(iteration_map.smooth, vec![0.25, 0.5]);
+        assert!(iteration_map.distance.is_empty());
+        assert_eq!(iteration_map.stripe, vec![1.5, 2.5]);
+        assert_eq!(iteration_map.glitched, vec![false, true]);
+        assert!(iteration_map.data_type() == DataType::Stripe);
+    }
+
+    #[test]
+    fn load_rejects_invalid_files() {
+        let path = temporary_path("load_rejects_invalid_files");
+
+        let mut truncated = header(2, 2, 0, "");
+        truncated.extend_from_slice(&[0; 31]);
+
+        let mut empty = header(0, 4, 0, "");
+        empty.extend_from_slice(&[0; 8]);
+
+        let mut newer = header(1, 1, 0, "");
+        newer[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        newer.extend_from_slice(&[0; 8]);
+
+        for bytes in [b"RFXX".to_vec(), truncated, empty, newer] {
+            std::fs::write(&path, &bytes).unwrap();
+            assert!(load_iteration_map(&path).is_err());
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
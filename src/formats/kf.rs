@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use config::Config;
+
+use crate::palette::Palette;
+use crate::settings::Settings;
+
+// Kalles Fraktaler expands the colour keys to a table of this many entries
+const KF_TABLE_SIZE: f64 = 1024.0;
+
+// The formulas that Kalles Fraktaler and this renderer have in common, by their KF number
+const KF_FRACTAL_TYPES: [(i64, &str); 2] = [(0, "mandelbrot"), (1, "burning_ship")];
+
+/// The `Key: Value` lines of a Kalles Fraktaler `.kfr` or `.kfp` file.
+pub fn parse_fields(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// The palette from the colour section of a Kalles Fraktaler file, along with the equivalent
+/// iteration span and offset.
+pub fn palette_from_fields(fields: &HashMap<String, String>) -> Result<(Palette, f64, f64), String> {
+    let values = fields.get("Colors").ok_or_else(|| "no Colors section".to_string())?
+        .split(',')
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse::<u8>().map_err(|e| format!("Colors: {}", e)))
+        .collect::<Result<Vec<u8>, String>>()?;
+
+    let mut colours = values.chunks_exact(3)
+        .map(|value| (value[0], value[1], value[2]))
+        .collect::<Vec<(u8, u8, u8)>>();
+
+    if colours.is_empty() {
+        return Err("Colors section is empty".to_string());
+    }
+
+    // The last key is interpolated back to the first
+    colours.push(colours[0]);
+
+    // Newer versions store a fractional divisor
+    let divide = fields.get("IterDiv")
+        .or_else(|| fields.get("Divide"))
+        .and_then(|value| value.parse::<f64>().ok())
+        .filter(|value| *value > 0.0)
+        .unwrap_or(1.0);
+
+    let offset = fields.get("ColorOffset")
+        .and_then(|value| value.parse::<f64>().ok())
+        .unwrap_or(0.0);
+
+    Ok((Palette::from_colours(&colours), KF_TABLE_SIZE * divide, (offset / KF_TABLE_SIZE).rem_euclid(1.0)))
+}
+
+/// A location in the same form as a configuration file, so that it can be merged like one.
+pub fn location_from_fields(fields: &HashMap<String, String>) -> Result<Config, String> {
+    let get = |key: &str| fields.get(key).ok_or_else(|| format!("missing {}", key));
+
+    let mut location = Config::default();
+
+    location.set("real", get("Re")?.as_str()).unwrap();
+    location.set("imag", get("Im")?.as_str()).unwrap();
+    location.set("zoom", get("Zoom")?.to_uppercase()).unwrap();
+
+    let iterations = get("Iterations")?.parse::<i64>().map_err(|e| format!("Iterations: {}", e))?;
+    location.set("iterations", iterations).unwrap();
+
+    if let Some(rotate) = fields.get("RotateAngle").and_then(|value| value.parse::<f64>().ok()) {
+        location.set("rotate", rotate.rem_euclid(360.0)).unwrap();
+    }
+
+    // Locations from before formulas could be chosen are of the quadratic Mandelbrot set
+    if let Some(fractal_type) = fields.get("FractalType") {
+        let number = fractal_type.parse::<i64>().map_err(|e| format!("FractalType: {}", e))?;
+
+        let fractal_type = KF_FRACTAL_TYPES.iter()
+            .find(|(kf_number, _)| *kf_number == number)
+            .map(|(_, name)| *name)
+            .ok_or_else(|| format!("FractalType {} is not supported", number))?;
+
+        location.set("fractal_type", fractal_type).unwrap();
+
+        let power = fields.get("Power")
+            .map(|value| value.parse::<i64>().map_err(|e| format!("Power: {}", e)))
+            .transpose()?
+            .unwrap_or(2);
+
+        location.set("fractal_power", power).unwrap();
+    }
+
+    // Locations without colours keep the current palette
+    if let Ok((palette, palette_iteration_span, palette_offset)) = palette_from_fields(fields) {
+        let colours = palette.colours().into_iter()
+            .flat_map(|(r, g, b)| vec![r as i64, g as i64, b as i64])
+            .collect::<Vec<i64>>();
+
+        location.set("palette", colours).unwrap();
+        location.set("palette_iteration_span", palette_iteration_span).unwrap();
+        location.set("palette_offset", palette_offset).unwrap();
+    }
+
+    Ok(location)
+}
+
+/// The location and palette in the settings as a Kalles Fraktaler location file.
+pub fn location_to_kfr(settings: &Settings, palette: &Palette) -> String {
+    let mut colours = palette.colours();
+
+    // Kalles Fraktaler interpolates the last key back to the first itself
+    if colours.len() > 2 && colours.first() == colours.last() {
+        colours.pop();
+    }
+
+    let colours = colours.into_iter()
+        .map(|(r, g, b)| format!("{},{},{},", r, g, b))
+        .collect::<String>();
+
+    let fractal_type = KF_FRACTAL_TYPES.iter()
+        .find(|(_, name)| name.eq_ignore_ascii_case(&settings.fractal_type))
+        .map(|(kf_number, _)| *kf_number)
+        .unwrap_or(0);
+
+    format!(
+        "Re: {}\r\nIm: {}\r\nZoom: {}\r\nIterations: {}\r\nFractalType: {}\r\nPower: {}\r\nIterDiv: {:.6}\r\nColorOffset: {}\r\nRotateAngle: {}\r\nSmooth: 1\r\nColors: {}\r\n",
+        settings.real,
+        settings.imag,
+        settings.zoom,
+        settings.iterations,
+        fractal_type,
+        settings.fractal_power,
+        settings.palette_iteration_span / KF_TABLE_SIZE,
+        (settings.palette_offset.rem_euclid(1.0) * KF_TABLE_SIZE).round() as i64,
+        settings.rotate,
+        colours)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn location_reads_kf_fields() {
+        let location = location_from_fields(&parse_fields(
+            "Re: -0.75\r\nIm: 0.1\r\nZoom: 1.5e10\r\nIterations: 5000\r\nRotateAngle: -90\r\nFractalType: 1\r\nPower: 3\r\n\
+            Colors: 255,0,0,0,0,255,\r\nIterDiv: 0.5\r\nColorOffset: 256\r\n")).unwrap();
+
+        assert_eq!(location.get_str("real").unwrap(), "-0.75");
+        assert_eq!(location.get_str("imag").unwrap(), "0.1");
+        assert_eq!(location.get_str("zoom").unwrap(), "1.5E10");
+        assert_eq!(location.get_int("iterations").unwrap(), 5000);
+        assert_eq!(location.get_float("rotate").unwrap(), 270.0);
+        assert_eq!(location.get_str("fractal_type").unwrap(), "burning_ship");
+        assert_eq!(location.get_int("fractal_power").unwrap(), 3);
+
+        // The first colour is repeated at the end, as Kalles Fraktaler wraps around
+        assert_eq!(location.get_array("palette").unwrap().len(), 9);
+        assert_eq!(location.get_float("palette_iteration_span").unwrap(), 512.0);
+        assert_eq!(location.get_float("palette_offset").unwrap(), 0.25);
+    }
+
+    #[test]
+    fn location_without_formula_or_colours() {
+        let location = location_from_fields(&parse_fields("Re: 0\r\nIm: 0\r\nZoom: 1\r\nIterations: 100\r\n")).unwrap();
+
+        assert!(location.get_str("fractal_type").is_err());
+        assert!(location.get_array("palette").is_err());
+    }
+
+    #[test]
+    fn location_rejects_invalid_fields() {
+        assert!(location_from_fields(&parse_fields("Re: 0\r\nIm: 0\r\nZoom: 1\r\n")).is_err());
+        assert!(location_from_fields(&parse_fields("Re: 0\r\nIm: 0\r\nZoom: 1\r\nIterations: many\r\n")).is_err());
+        assert!(location_from_fields(&parse_fields("Re: 0\r\nIm: 0\r\nZoom: 1\r\nIterations: 100\r\nFractalType: 99\r\n")).is_err());
+    }
+
+    #[test]
+    fn kfr_round_trip() {
+        let mut settings = Settings::default();
+
+        settings.real = "-0.75".to_string();
+        settings.imag = "0.1".to_string();
+        settings.zoom = "1E5".to_string();
+        settings.iterations = 2000;
+        settings.rotate = 45.0;
+        settings.fractal_type = "burning_ship".to_string();
+        settings.fractal_power = 2;
+        settings.palette_iteration_span = 512.0;
+        settings.palette_offset = 0.25;
+
+        let palette = Palette::from_colours(&[(255, 0, 0), (0, 255, 0), (0, 0, 255), (255, 0, 0)]);
+
+        let fields = parse_fields(&location_to_kfr(&settings, &palette));
+        let location = location_from_fields(&fields).unwrap();
+
+        assert_eq!(location.get_str("real").unwrap(), settings.real);
+        assert_eq!(location.get_str("imag").unwrap(), settings.imag);
+        assert_eq!(location.get_str("zoom").unwrap(), settings.zoom);
+        assert_eq!(location.get_int("iterations").unwrap(), 2000);
+        assert_eq!(location.get_float("rotate").unwrap(), 45.0);
+        assert_eq!(location.get_str("fractal_type").unwrap(), "burning_ship");
+
+        let (imported, iteration_span, offset) = palette_from_fields(&fields).unwrap();
+
+        assert_eq!(imported.colours(), palette.colours());
+        assert_eq!(iteration_span, 512.0);
+        assert_eq!(offset, 0.25);
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::Path;
+
+use config::{Config, File, FileFormat, Value};
+
+use rust_fractal::util::data_export::DataExport;
+
+use crate::palette::Palette;
+use crate::settings::{Settings, migrate_settings, validate_settings, version_warning};
+use super::kf;
+use super::png_metadata::{read_png_text, CONFIGURATION_KEYWORD};
+
+/// Describes what changed when a location file was merged into the current settings.
+pub struct LoadedLocation {
+    pub reset_renderer: bool,
+    pub quick_reset: bool,
+    pub palette: Option<Palette>,
+    /// Problems with the file that did not stop it from loading
+    pub warnings: Vec<String>,
+}
+
+/// Reads a configuration file, a Kalles Fraktaler location which is converted to the same keys, or the
+/// configuration embedded in a PNG saved by this program.
+pub fn load_location_file(path: &Path) -> Result<Config, String> {
+    if is_kfr_file(path) {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        return kf::location_from_fields(&kf::parse_fields(&text));
+    }
+
+    if is_png_file(path) {
+        let text = read_png_text(path)?
+            .remove(CONFIGURATION_KEYWORD)
+            .ok_or_else(|| "the image does not contain a location".to_string())?;
+
+        return parse_location(&text);
+    }
+
+    let mut location = Config::default();
+    location.merge(File::from(path)).map_err(|e| e.to_string())?;
+
+    migrate_settings(&mut location);
+    validate_settings(&location)?;
+
+    Ok(location)
+}
+
+/// Reads a configuration stored as text inside another file.
+pub fn parse_location(text: &str) -> Result<Config, String> {
+    let mut location = Config::default();
+
+    location.merge(File::from_str(text, FileFormat::Toml)).map_err(|e| e.to_string())?;
+
+    migrate_settings(&mut location);
+    validate_settings(&location)?;
+
+    Ok(location)
+}
+
+pub fn is_kfr_file(path: &Path) -> bool {
+    matches!(path.extension().and_then(|extension| extension.to_str()), Some(extension) if extension.eq_ignore_ascii_case("kfr"))
+}
+
+pub fn is_png_file(path: &Path) -> bool {
+    matches!(path.extension().and_then(|extension| extension.to_str()), Some(extension) if extension.eq_ignore_ascii_case("png"))
+}
+
+/// Merges a location or configuration file into the current settings. The GUI and the headless
+/// renderer both use this so that a file always produces the same render.
+pub fn merge_location(settings: &mut Settings, new_settings: Config) -> Result<LoadedLocation, String> {
+    let contains = |key: &str| new_settings.get::<Value>(key).is_ok();
+
+    let mut warnings = version_warning(&new_settings).into_iter().collect::<Vec<String>>();
+
+    let reset_renderer = ["real", "imag", "zoom", "iterations", "rotate"].iter().any(|key| contains(key));
+    let quick_reset = ["image_width", "image_height", "approximation_order"].iter().any(|key| contains(key));
+
+    let previous_formula = (settings.fractal_type.clone(), settings.fractal_power);
+
+    settings.merge_config(&new_settings)?;
+
+    if !contains("rotate") {
+        settings.rotate = 0.0;
+    }
+
+    // Locations without a formula are of the quadratic Mandelbrot set
+    if !contains("fractal_type") {
+        settings.fractal_type = "mandelbrot".to_string();
+    }
+
+    if !contains("fractal_power") {
+        settings.fractal_power = 2;
+    }
+
+    let reset_renderer = reset_renderer || previous_formula != (settings.fractal_type.clone(), settings.fractal_power);
+
+    let palette = Palette::from_config(&new_settings, &mut warnings);
+
+    // Only reset these if the palette is defined
+    if palette.is_some() {
+        if !contains("palette_iteration_span") {
+            settings.palette_iteration_span = 1.0;
+        }
+
+        if !contains("palette_offset") {
+            settings.palette_offset = 0.0;
+        }
+    }
+
+    Ok(LoadedLocation {
+        reset_renderer,
+        quick_reset,
+        palette,
+        warnings,
+    })
+}
+
+/// Applies a palette loaded from a file, using the colouring and lighting options in the settings.
+pub fn apply_palette(data_export: &mut DataExport, settings: &Settings, palette: &Palette) {
+    data_export.change_palette(
+        Some(palette.colours()),
+        settings.palette_iteration_span as f32,
+        settings.palette_offset as f32,
+        settings.distance_transition as f32,
+        settings.distance_color,
+        settings.palette_cyclic,
+        settings.lighting
+    );
+
+    data_export.change_lighting(
+        settings.lighting_direction as f32,
+        settings.lighting_azimuth as f32,
+        settings.lighting_opacity as f32,
+        settings.lighting_ambient as f32,
+        settings.lighting_diffuse as f32,
+        settings.lighting_specular as f32,
+        settings.lighting_shininess as i32
+    );
+}
@@ -0,0 +1,15 @@
+mod location;
+mod kf;
+mod gradient;
+mod png_metadata;
+mod png_stream;
+mod iteration_map;
+mod openexr;
+
+pub use location::{LoadedLocation, load_location_file, is_kfr_file, is_png_file, parse_location, merge_location, apply_palette};
+pub use png_metadata::{TextChunk, CONFIGURATION_KEYWORD, location_text_chunks, write_png_text, read_png_text};
+pub use png_stream::PngStreamWriter;
+pub use kf::location_to_kfr;
+pub use gradient::{ImportedPalette, PALETTE_EXTENSIONS, is_palette_file, load_palette_file};
+pub use iteration_map::{IterationMap, ITERATION_MAP_EXTENSION, is_iteration_map_file, save_iteration_map, load_iteration_map};
+pub use openexr::{is_exr_file, save_exr};
@@ -0,0 +1,77 @@
+use std::path::Path;
+
+use exr::prelude::*;
+
+use rust_fractal::util::data_export::DataExport;
+
+use crate::export::srgb_to_linear;
+
+/// Files saved as OpenEXR rather than through the image encoders of the renderer.
+pub fn is_exr_file(path: &Path) -> bool {
+    matches!(path.extension().and_then(|extension| extension.to_str()), Some(extension) if extension.eq_ignore_ascii_case("exr"))
+}
+
+/// Writes the colour of the image along with the values it was coloured from, so that it can be
+/// rebuilt or recoloured in compositing software. The colour is in the default layer, and the other
+/// values are in the `smooth`, `distance`, `stripe` and `glitch` layers. Distance and stripe values
+/// are only written if the current coloring method calculated them, and the glitch mask only if it
+/// was stored.
+pub fn save_exr(path: &Path, data_export: &DataExport) -> std::result::Result<(), String> {
+    let width = data_export.image_width;
+    let height = data_export.image_height;
+    let n = width * height;
+
+    if n == 0 || data_export.buffer.len() < 3 * n || data_export.iterations.len() != n || data_export.smooth.len() != n {
+        return Err("there is no complete render to export".to_string());
+    }
+
+    let colour = |channel: usize| data_export.buffer.chunks_exact(3)
+        .take(n)
+        .map(|rgb| srgb_to_linear(rgb[channel]))
+        .collect::<Vec<f32>>();
+
+    // The continuous iteration count, with interior pixels at the iteration limit
+    let smooth = data_export.iterations.iter()
+        .zip(data_export.smooth.iter())
+        .map(|(iterations, smooth)| if *smooth <= 1.0 {
+            *iterations as f32 + *smooth
+        } else {
+            *iterations as f32
+        })
+        .collect::<Vec<f32>>();
+
+    let has_distance = data_export.distance.len() == n;
+    let has_stripe = data_export.stripe.len() == n;
+
+    let mut channels = vec![
+        AnyChannel::new("R", FlatSamples::F32(colour(0))),
+        AnyChannel::new("G", FlatSamples::F32(colour(1))),
+        AnyChannel::new("B", FlatSamples::F32(colour(2))),
+        AnyChannel::new("smooth.Y", FlatSamples::F32(smooth)),
+    ];
+
+    if data_export.glitched.len() == n {
+        let glitch = data_export.glitched.iter().map(|glitched| if *glitched { 1.0 } else { 0.0 }).collect();
+        channels.push(AnyChannel::new("glitch.Y", FlatSamples::F32(glitch)));
+    }
+
+    if has_distance {
+        channels.push(AnyChannel::new("distance.Y", FlatSamples::F32(data_export.distance.clone())));
+    }
+
+    if has_stripe {
+        channels.push(AnyChannel::new("stripe.Y", FlatSamples::F32(data_export.stripe.clone())));
+    }
+
+    let layer = Layer::new(
+        (width, height),
+        LayerAttributes::named("rust-fractal"),
+        Encoding::SMALL_LOSSLESS,
+        AnyChannels::sort(SmallVec::from_vec(channels)),
+    );
+
+    Image::from_layer(layer)
+        .write()
+        .to_file(path)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+
+use crate::palette::Palette;
+use crate::settings::{Settings, serialize_settings};
+
+pub const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+// The iTXt keyword holding the full configuration, which is read back when the image is opened
+pub const CONFIGURATION_KEYWORD: &str = "rust-fractal";
+
+/// A text chunk to add to a PNG. ASCII text is written as tEXt and anything else as uncompressed iTXt.
+pub struct TextChunk {
+    pub keyword: String,
+    pub text: String,
+}
+
+impl TextChunk {
+    pub fn new(keyword: &str, text: String) -> Self {
+        TextChunk {
+            keyword: keyword.to_string(),
+            text,
+        }
+    }
+
+    pub fn encode(&self) -> (&'static [u8; 4], Vec<u8>) {
+        let mut data = self.keyword.as_bytes().to_vec();
+        data.push(0);
+
+        if self.text.is_ascii() {
+            data.extend_from_slice(self.text.as_bytes());
+            (b"tEXt", data)
+        } else {
+            // No compression, with empty language and translated keyword
+            data.extend_from_slice(&[0, 0, 0, 0]);
+            data.extend_from_slice(self.text.as_bytes());
+            (b"iTXt", data)
+        }
+    }
+}
+
+/// The chunks that describe the location of an image saved by this program. The full configuration
+/// is read back when the image is opened, and the description is for other image viewers.
+pub fn location_text_chunks(settings: &Settings, palette: &Palette) -> Vec<TextChunk> {
+    let description = format!(
+        "Re: {}\nIm: {}\nZoom: {}\nIterations: {}\nRotation: {}",
+        settings.real,
+        settings.imag,
+        settings.zoom,
+        settings.iterations,
+        settings.rotate);
+
+    vec![
+        TextChunk::new("Software", format!("rust-fractal-gui {}", env!("CARGO_PKG_VERSION"))),
+        TextChunk::new("Description", description),
+        TextChunk::new(CONFIGURATION_KEYWORD, serialize_settings(settings, palette)),
+    ]
+}
+
+// The type and data of each chunk, along with its position in the file
+fn read_chunks(bytes: &[u8]) -> Result<Vec<(usize, [u8; 4], &[u8])>, String> {
+    if bytes.len() < PNG_SIGNATURE.len() || bytes[..PNG_SIGNATURE.len()] != PNG_SIGNATURE {
+        return Err("not a PNG file".to_string());
+    }
+
+    let mut chunks = Vec::new();
+    let mut position = PNG_SIGNATURE.len();
+
+    while position + 12 <= bytes.len() {
+        let length = u32::from_be_bytes([bytes[position], bytes[position + 1], bytes[position + 2], bytes[position + 3]]) as usize;
+        let chunk_type = [bytes[position + 4], bytes[position + 5], bytes[position + 6], bytes[position + 7]];
+
+        let data = bytes.get((position + 8)..(position + 8 + length)).ok_or_else(|| "truncated chunk".to_string())?;
+        chunks.push((position, chunk_type, data));
+
+        position += 12 + length;
+    }
+
+    Ok(chunks)
+}
+
+/// Writes a chunk with its length and CRC.
+pub fn write_chunk(writer: &mut impl Write, chunk_type: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    // The CRC covers the chunk type and data
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(chunk_type);
+    hasher.update(data);
+
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(chunk_type)?;
+    writer.write_all(data)?;
+    writer.write_all(&hasher.finalize().to_be_bytes())
+}
+
+/// Adds text chunks to an existing PNG, just before the end of the image.
+pub fn write_png_text(path: &Path, text_chunks: &[TextChunk]) -> Result<(), String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+
+    let end = read_chunks(&bytes)?.into_iter()
+        .find(|(_, chunk_type, _)| chunk_type == b"IEND")
+        .map(|(position, _, _)| position)
+        .ok_or_else(|| "missing IEND chunk".to_string())?;
+
+    let mut output = bytes[..end].to_vec();
+
+    for text_chunk in text_chunks {
+        let (chunk_type, data) = text_chunk.encode();
+        write_chunk(&mut output, chunk_type, &data).map_err(|e| e.to_string())?;
+    }
+
+    output.extend_from_slice(&bytes[end..]);
+
+    std::fs::write(path, output).map_err(|e| e.to_string())
+}
+
+/// The uncompressed tEXt and iTXt chunks of a PNG, by keyword.
+pub fn read_png_text(path: &Path) -> Result<HashMap<String, String>, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+
+    let mut output = HashMap::new();
+
+    for (_, chunk_type, data) in read_chunks(&bytes)? {
+        let separator = match data.iter().position(|byte| *byte == 0) {
+            Some(separator) => separator,
+            None => continue
+        };
+
+        let keyword = String::from_utf8_lossy(&data[..separator]).to_string();
+        let rest = &data[(separator + 1)..];
+
+        match &chunk_type {
+            // Latin-1 maps directly onto the first 256 code points
+            b"tEXt" => {
+                output.insert(keyword, rest.iter().map(|byte| *byte as char).collect());
+            },
+            b"iTXt" => {
+                // Compressed text is not written by this program
+                if rest.len() < 2 || rest[0] != 0 {
+                    continue;
+                }
+
+                // Skip the language tag and translated keyword
+                let text = rest[2..].splitn(3, |byte| *byte == 0).nth(2);
+
+                if let Some(text) = text {
+                    output.insert(keyword, String::from_utf8_lossy(text).to_string());
+                }
+            },
+            _ => {}
+        }
+    }
+
+    Ok(output)
+}
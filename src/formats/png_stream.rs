@@ -0,0 +1,159 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+
+use super::png_metadata::{TextChunk, PNG_SIGNATURE, write_chunk};
+
+// The compressed image data is split into IDAT chunks of this size
+const CHUNK_SIZE: usize = 1 << 20;
+
+// Collects the compressed image data and writes it out as IDAT chunks
+struct ChunkWriter {
+    output: BufWriter<File>,
+    buffer: Vec<u8>,
+}
+
+impl ChunkWriter {
+    fn write_buffer(&mut self) -> std::io::Result<()> {
+        if !self.buffer.is_empty() {
+            write_chunk(&mut self.output, b"IDAT", &self.buffer)?;
+            self.buffer.clear();
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(data);
+
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.write_buffer()?;
+        }
+
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.write_buffer()?;
+        self.output.flush()
+    }
+}
+
+/// Writes an 8 bit RGB PNG a few rows at a time, so that images larger than memory can be saved.
+pub struct PngStreamWriter {
+    encoder: ZlibEncoder<ChunkWriter>,
+    width: usize,
+    remaining_rows: usize,
+    previous_row: Vec<u8>,
+}
+
+impl PngStreamWriter {
+    /// Creates the file and writes the header and text chunks.
+    pub fn new(path: &Path, width: usize, height: usize, text_chunks: &[TextChunk]) -> Result<Self, String> {
+        if width == 0 || height == 0 || width > i32::MAX as usize || height > i32::MAX as usize {
+            return Err(format!("a PNG cannot be {}x{} pixels", width, height));
+        }
+
+        let start = || -> std::io::Result<BufWriter<File>> {
+            let mut output = BufWriter::new(File::create(path)?);
+
+            output.write_all(&PNG_SIGNATURE)?;
+
+            // Bit depth 8, RGB colour, default compression and filtering, not interlaced
+            let mut header = Vec::with_capacity(13);
+            header.extend_from_slice(&(width as u32).to_be_bytes());
+            header.extend_from_slice(&(height as u32).to_be_bytes());
+            header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+            write_chunk(&mut output, b"IHDR", &header)?;
+
+            for text_chunk in text_chunks {
+                let (chunk_type, data) = text_chunk.encode();
+                write_chunk(&mut output, chunk_type, &data)?;
+            }
+
+            Ok(output)
+        };
+
+        let output = start().map_err(|e| e.to_string())?;
+
+        Ok(PngStreamWriter {
+            encoder: ZlibEncoder::new(ChunkWriter {
+                output,
+                buffer: Vec::with_capacity(CHUNK_SIZE),
+            }, Compression::default()),
+            width,
+            remaining_rows: height,
+            previous_row: vec![0u8; 3 * width],
+        })
+    }
+
+    /// Adds whole rows of RGB pixels to the image, starting from the top.
+    pub fn write_rows(&mut self, rows: &[u8]) -> Result<(), String> {
+        let row_length = 3 * self.width;
+
+        if rows.len() % row_length != 0 || rows.len() / row_length > self.remaining_rows {
+            return Err("the rows do not fit in the image".to_string());
+        }
+
+        let mut filtered = vec![0u8; row_length + 1];
+
+        for row in rows.chunks_exact(row_length) {
+            // Each row uses the Paeth filter, which works well for the smooth gradients in renders
+            filtered[0] = 4;
+
+            for k in 0..row_length {
+                let left = if k >= 3 { row[k - 3] } else { 0 };
+                let up = self.previous_row[k];
+                let up_left = if k >= 3 { self.previous_row[k - 3] } else { 0 };
+
+                filtered[k + 1] = row[k].wrapping_sub(paeth(left, up, up_left));
+            }
+
+            self.encoder.write_all(&filtered).map_err(|e| e.to_string())?;
+            self.previous_row.copy_from_slice(row);
+        }
+
+        self.remaining_rows -= rows.len() / row_length;
+
+        Ok(())
+    }
+
+    /// Completes the image. All of the rows must have been written.
+    pub fn finish(self) -> Result<(), String> {
+        if self.remaining_rows > 0 {
+            return Err(format!("{} rows were not written", self.remaining_rows));
+        }
+
+        let finish = || -> std::io::Result<()> {
+            let mut chunk_writer = self.encoder.finish()?;
+            chunk_writer.write_buffer()?;
+
+            write_chunk(&mut chunk_writer.output, b"IEND", &[])?;
+            chunk_writer.output.flush()
+        };
+
+        finish().map_err(|e| e.to_string())
+    }
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+
+    let distance_left = (estimate - left as i16).abs();
+    let distance_up = (estimate - up as i16).abs();
+    let distance_up_left = (estimate - up_left as i16).abs();
+
+    if distance_left <= distance_up && distance_left <= distance_up_left {
+        left
+    } else if distance_up <= distance_up_left {
+        up
+    } else {
+        up_left
+    }
+}
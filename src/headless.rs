@@ -0,0 +1,55 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+use rust_fractal::renderer::FractalRenderer;
+
+use crate::formats::{load_location_file, merge_location, apply_palette};
+use crate::palette::Palette;
+use crate::settings::{Settings, load_settings};
+
+/// The settings for rendering a location file outside of the GUI. The defaults are loaded from start.toml and the
+/// location is merged using the same rules as opening a file in the GUI. Any warnings are for the caller to show.
+pub fn load_location_settings(location: &Path) -> Result<(Settings, Option<Palette>, Vec<String>), String> {
+    let (mut settings, start_palette, mut warnings) = load_settings(Path::new("start.toml"))?;
+
+    let new_settings = load_location_file(location).map_err(|e| format!("Error reading {}: {}", location.display(), e))?;
+
+    let loaded = merge_location(&mut settings, new_settings).map_err(|e| format!("Error in {}: {}", location.display(), e))?;
+    warnings.extend(loaded.warnings);
+
+    // The palette in the location takes priority over the default one
+    Ok((settings, loaded.palette.or(start_palette), warnings))
+}
+
+/// Renders a location file without opening a window.
+pub fn render_headless(location: &Path, output: &Path) -> Result<(), String> {
+    // The output is checked first so that a long render is not wasted
+    let output_path = output.to_str().ok_or_else(|| format!("Invalid output path: {}", output.display()))?;
+
+    if !output.parent().map_or(true, |parent| parent.as_os_str().is_empty() || parent.exists()) {
+        return Err(format!("The directory of {} does not exist", output.display()));
+    }
+
+    let (settings, palette, warnings) = load_location_settings(location)?;
+
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
+    if settings.show_output {
+        println!(" {:<15}| {:<15}| {:<15}| {:<6}| {:<15}| {:<15}| {:<15}| {:<6}| {:<15}", "Zoom", "Approx [ms]", "Skipped [it]", "Order", "Maximum [it]", "Iteration [ms]", "Correct [ms]", "Ref", "Frame [ms]");
+    };
+
+    let mut renderer = FractalRenderer::new(settings.to_config());
+
+    if let Some(palette) = palette {
+        apply_palette(&mut renderer.data_export.lock(), &settings, &palette);
+    }
+
+    renderer.render_frame(0, String::from(""), Arc::new(AtomicBool::new(false)));
+
+    renderer.data_export.lock().save_colour(output_path);
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use std::collections::VecDeque;
+
+use crate::settings::Settings;
+
+// The maximum number of locations that can be gone back through
+const HISTORY_LIMIT: usize = 100;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct LocationSnapshot {
+    pub real: String,
+    pub imag: String,
+    pub zoom: String,
+    pub iterations: usize,
+    pub rotation: f64,
+    pub fractal_type: String,
+    pub fractal_power: i64,
+}
+
+impl LocationSnapshot {
+    pub fn from_settings(settings: &Settings) -> Self {
+        LocationSnapshot {
+            real: settings.real.clone(),
+            imag: settings.imag.clone(),
+            zoom: settings.zoom.clone(),
+            iterations: settings.iterations,
+            rotation: settings.rotate,
+            fractal_type: settings.fractal_type.clone(),
+            fractal_power: settings.fractal_power,
+        }
+    }
+
+    pub fn apply(&self, settings: &mut Settings) {
+        settings.real = self.real.clone();
+        settings.imag = self.imag.clone();
+        settings.zoom = self.zoom.clone();
+        settings.iterations = self.iterations;
+        settings.rotate = self.rotation;
+        settings.fractal_type = self.fractal_type.clone();
+        settings.fractal_power = self.fractal_power;
+    }
+}
+
+/// A bounded undo/redo stack of the locations that have been navigated away from.
+pub struct NavigationHistory {
+    back: VecDeque<LocationSnapshot>,
+    forward: Vec<LocationSnapshot>,
+}
+
+impl NavigationHistory {
+    pub fn new() -> Self {
+        NavigationHistory {
+            back: VecDeque::new(),
+            forward: Vec::new(),
+        }
+    }
+
+    /// Records the location before it is changed. This clears the forward history.
+    pub fn push(&mut self, snapshot: LocationSnapshot) {
+        self.forward.clear();
+
+        if self.back.back() == Some(&snapshot) {
+            return;
+        }
+
+        self.back.push_back(snapshot);
+
+        if self.back.len() > HISTORY_LIMIT {
+            self.back.pop_front();
+        }
+    }
+
+    pub fn back(&mut self, current: LocationSnapshot) -> Option<LocationSnapshot> {
+        let previous = self.back.pop_back()?;
+        self.forward.push(current);
+
+        Some(previous)
+    }
+
+    pub fn forward(&mut self, current: LocationSnapshot) -> Option<LocationSnapshot> {
+        let next = self.forward.pop()?;
+        self.back.push_back(current);
+
+        Some(next)
+    }
+}
+
+impl Default for NavigationHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -4,4 +4,17 @@ pub mod theme;
 pub mod commands;
 pub mod lens;
 pub mod custom;
-pub mod render_thread;
\ No newline at end of file
+pub mod render_thread;
+pub mod progress;
+pub mod history;
+pub mod bookmarks;
+pub mod pixel;
+pub mod sequence;
+pub mod palette;
+pub mod settings;
+pub mod notifications;
+pub mod formats;
+pub mod headless;
+pub mod export;
+pub mod batch;
+pub mod preview;
\ No newline at end of file
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use druid::{Data, Lens};
+
+// The number of notifications kept in the log
+pub const LOG_LIMIT: usize = 100;
+
+// How long a notification is shown over the side panel
+pub const TOAST_DURATION: Duration = Duration::from_secs(5);
+
+#[derive(Data, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NotificationLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+impl NotificationLevel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NotificationLevel::Info => "INFO",
+            NotificationLevel::Warning => "WARNING",
+            NotificationLevel::Error => "ERROR",
+        }
+    }
+}
+
+#[derive(Data, Clone, PartialEq, Lens, Debug)]
+pub struct Notification {
+    pub level: NotificationLevel,
+    pub message: String,
+    /// The local time the notification was created
+    pub time: String,
+}
+
+impl Notification {
+    pub fn new(level: NotificationLevel, message: impl Into<String>) -> Self {
+        Notification {
+            level,
+            message: message.into(),
+            time: chrono::Local::now().format("%H:%M:%S").to_string(),
+        }
+    }
+
+    pub fn info(message: impl Into<String>) -> Self {
+        Notification::new(NotificationLevel::Info, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Notification::new(NotificationLevel::Warning, message)
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Notification::new(NotificationLevel::Error, message)
+    }
+}
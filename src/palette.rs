@@ -0,0 +1,307 @@
+use std::convert::TryFrom;
+use std::path::Path;
+use std::sync::Arc;
+
+use config::Config;
+use druid::{Data, Lens};
+use rust_fractal::util::data_export::DataExport;
+
+// The number of colours passed to the renderer when the stops have to be sampled
+const PALETTE_SAMPLES: usize = 256;
+
+// Written with every saved palette so the order of the flat colour array is never ambiguous
+const CHANNEL_ORDER: &str = "rgb";
+
+#[derive(Data, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaletteInterpolation {
+    /// Evenly spaced stops are passed straight to the renderer, which does its own interpolation
+    Default,
+    Linear,
+    Smooth,
+    Step,
+}
+
+impl PaletteInterpolation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PaletteInterpolation::Default => "default",
+            PaletteInterpolation::Linear => "linear",
+            PaletteInterpolation::Smooth => "smooth",
+            PaletteInterpolation::Step => "step",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Self {
+        match name.to_ascii_lowercase().as_ref() {
+            "linear" => PaletteInterpolation::Linear,
+            "smooth" => PaletteInterpolation::Smooth,
+            "step" => PaletteInterpolation::Step,
+            _ => PaletteInterpolation::Default,
+        }
+    }
+}
+
+#[derive(Data, Clone, PartialEq, Lens, Debug)]
+pub struct PaletteStop {
+    /// Position of the stop along the palette, between 0 and 1
+    pub position: f64,
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl PaletteStop {
+    pub fn new(position: f64, (red, green, blue): (u8, u8, u8)) -> Self {
+        PaletteStop {
+            position,
+            red,
+            green,
+            blue,
+        }
+    }
+
+    pub fn colour(&self) -> (u8, u8, u8) {
+        (self.red, self.green, self.blue)
+    }
+}
+
+/// A palette defined by its control points, as opposed to the interpolated buffer held by the renderer.
+#[derive(Data, Clone, PartialEq, Lens, Debug)]
+pub struct Palette {
+    pub stops: Arc<Vec<PaletteStop>>,
+    pub interpolation: PaletteInterpolation,
+}
+
+impl Palette {
+    /// Evenly spaced stops, as used by the flat `palette` arrays in configuration files.
+    pub fn from_colours(colours: &[(u8, u8, u8)]) -> Self {
+        let divisor = (colours.len().max(2) - 1) as f64;
+
+        Palette {
+            stops: Arc::new(colours.iter().enumerate().map(|(i, colour)| {
+                PaletteStop::new(i as f64 / divisor, *colour)
+            }).collect()),
+            interpolation: PaletteInterpolation::Default,
+        }
+    }
+
+    /// The control colours the renderer is currently using.
+    pub fn from_data_export(data_export: &DataExport) -> Self {
+        let colours = data_export.palette_buffer.iter().map(|value| {
+            let (r, g, b, _) = value.rgba_u8();
+            (r, g, b)
+        }).collect::<Vec<(u8, u8, u8)>>();
+
+        Palette::from_colours(&colours)
+    }
+
+    /// The palette defined by the `palette` keys of a configuration file, if there is one. Problems that
+    /// could be worked around are added to the warnings.
+    pub fn from_config(config: &Config, warnings: &mut Vec<String>) -> Option<Self> {
+        let colour_values = config.get_array("palette").ok()?;
+
+        let channel_order = config.get_str("palette_channel_order").unwrap_or_else(|_| CHANNEL_ORDER.to_string());
+
+        let swap_channels = match channel_order.to_ascii_lowercase().as_ref() {
+            "rgb" => false,
+            "bgr" => true,
+            _ => {
+                warnings.push(format!("Unknown palette channel order {}, assuming rgb", channel_order));
+                false
+            }
+        };
+
+        // A palette with any value that is not a channel value is not used at all
+        let values = colour_values.into_iter()
+            .enumerate()
+            .map(|(index, value)| value.into_int().ok()
+                .and_then(|value| u8::try_from(value).ok())
+                .ok_or_else(|| format!("Palette value {} is not a whole number from 0 to 255, keeping the current palette", index + 1)))
+            .collect::<Result<Vec<u8>, String>>();
+
+        let values = match values {
+            Ok(values) => values,
+            Err(warning) => {
+                warnings.push(warning);
+                return None;
+            }
+        };
+
+        if values.len() % 3 != 0 {
+            warnings.push(format!("The palette has {} values, which is not a whole number of colours, so the last {} are ignored", values.len(), values.len() % 3));
+        }
+
+        if values.len() < 3 {
+            warnings.push("The palette has no colours, keeping the current palette".to_string());
+            return None;
+        }
+
+        let colours = values.chunks_exact(3).map(|value| {
+            if swap_channels {
+                (value[2], value[1], value[0])
+            } else {
+                (value[0], value[1], value[2])
+            }
+        }).collect::<Vec<(u8, u8, u8)>>();
+
+        let mut palette = Palette::from_colours(&colours);
+
+        // Palettes saved from the editor also store the position of each stop
+        if let Ok(positions) = config.get_array("palette_positions") {
+            if positions.len() == colours.len() {
+                let stops = Arc::make_mut(&mut palette.stops);
+
+                for (stop, position) in stops.iter_mut().zip(positions) {
+                    stop.position = position.into_float().unwrap_or(stop.position);
+                }
+            }
+        }
+
+        if let Ok(interpolation) = config.get_str("palette_interpolation") {
+            palette.interpolation = PaletteInterpolation::from_name(&interpolation);
+        }
+
+        Some(palette)
+    }
+
+    pub fn sorted_stops(&self) -> Vec<PaletteStop> {
+        let mut stops = self.stops.as_ref().clone();
+        stops.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap_or(std::cmp::Ordering::Equal));
+
+        stops
+    }
+
+    fn is_evenly_spaced(stops: &[PaletteStop]) -> bool {
+        let divisor = (stops.len().max(2) - 1) as f64;
+
+        stops.iter().enumerate().all(|(i, stop)| (stop.position - i as f64 / divisor).abs() < 1e-6)
+    }
+
+    /// Evenly spaced samples along the whole palette.
+    pub fn samples(&self, count: usize) -> Vec<(u8, u8, u8)> {
+        let stops = self.sorted_stops();
+        let divisor = (count.max(2) - 1) as f64;
+
+        (0..count).map(|i| sample_stops(&stops, self.interpolation, i as f64 / divisor)).collect()
+    }
+
+    /// The colours to pass to `DataExport::change_palette`.
+    pub fn colours(&self) -> Vec<(u8, u8, u8)> {
+        let stops = self.sorted_stops();
+
+        if stops.is_empty() {
+            return vec![(0, 0, 0)];
+        }
+
+        if self.interpolation == PaletteInterpolation::Default && Palette::is_evenly_spaced(&stops) {
+            return stops.iter().map(|stop| stop.colour()).collect();
+        }
+
+        self.samples(PALETTE_SAMPLES)
+    }
+
+    /// Adds a stop in the middle of the largest gap between stops, coloured to match the palette at that point.
+    pub fn add_stop(&mut self) {
+        let stops = self.sorted_stops();
+
+        let position = if stops.len() < 2 {
+            0.5
+        } else {
+            let (start, end) = stops.windows(2)
+                .map(|pair| (pair[0].position, pair[1].position))
+                .fold((0.0, 0.0), |largest, gap| if gap.1 - gap.0 > largest.1 - largest.0 { gap } else { largest });
+
+            0.5 * (start + end)
+        };
+
+        let stop = PaletteStop::new(position, sample_stops(&stops, self.interpolation, position));
+
+        let mut stops = stops;
+        stops.push(stop);
+        stops.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap_or(std::cmp::Ordering::Equal));
+
+        self.stops = Arc::new(stops);
+    }
+
+    pub fn remove_stop(&mut self, stop: &PaletteStop) {
+        // There must always be a colour to render with
+        if self.stops.len() <= 1 {
+            return;
+        }
+
+        let stops = Arc::make_mut(&mut self.stops);
+
+        if let Some(index) = stops.iter().position(|other| other == stop) {
+            stops.remove(index);
+        }
+    }
+
+    /// The stops as configuration file keys, which `from_config` reads back to exactly the same palette.
+    pub fn to_toml(&self) -> String {
+        let stops = self.sorted_stops();
+
+        let colours = stops.iter()
+            .flat_map(|stop| vec![stop.red, stop.green, stop.blue])
+            .collect::<Vec<u8>>();
+
+        let positions = stops.iter()
+            .map(|stop| stop.position)
+            .collect::<Vec<f64>>();
+
+        format!(
+            "palette = {:?}\npalette_positions = {:?}\npalette_interpolation = \"{}\"\npalette_channel_order = \"{}\"",
+            colours,
+            positions,
+            self.interpolation.name(),
+            CHANNEL_ORDER)
+    }
+
+    /// Writes a standalone palette file that can be opened like any other configuration file.
+    pub fn save(&self, path: &Path, palette_iteration_span: f64, palette_offset: f64) -> Result<(), String> {
+        let output = format!(
+            "{}\npalette_iteration_span = {}\npalette_offset = {}\n",
+            self.to_toml(),
+            palette_iteration_span,
+            palette_offset);
+
+        std::fs::write(path, output).map_err(|e| e.to_string())
+    }
+}
+
+fn sample_stops(stops: &[PaletteStop], interpolation: PaletteInterpolation, position: f64) -> (u8, u8, u8) {
+    let first = match stops.first() {
+        Some(first) => first,
+        None => return (0, 0, 0),
+    };
+
+    let last = stops.last().unwrap();
+
+    if position <= first.position {
+        return first.colour();
+    }
+
+    if position >= last.position {
+        return last.colour();
+    }
+
+    let index = stops.iter().rposition(|stop| stop.position <= position).unwrap();
+    let (start, end) = (&stops[index], &stops[index + 1]);
+
+    let width = end.position - start.position;
+
+    let t = if width > 0.0 {
+        (position - start.position) / width
+    } else {
+        1.0
+    };
+
+    let t = match interpolation {
+        PaletteInterpolation::Step => 0.0,
+        PaletteInterpolation::Smooth => t * t * (3.0 - 2.0 * t),
+        _ => t,
+    };
+
+    let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+
+    (mix(start.red, end.red), mix(start.green, end.green), mix(start.blue, end.blue))
+}
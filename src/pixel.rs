@@ -0,0 +1,114 @@
+use rust_fractal::renderer::FractalRenderer;
+use rust_fractal::util::{ComplexFixed, ComplexExtended, FloatArbitrary, get_delta_top_left, string_to_extended};
+use rust_fractal::util::data_export::DataExport;
+
+use crate::settings::Settings;
+
+// The width and height of the magnified area around the inspected pixel
+pub const LOUPE_SIZE: usize = 9;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PixelSample {
+    pub iterations: u32,
+    pub smooth: f32,
+    pub distance: f32,
+    pub stripe: f32,
+    pub glitched: bool,
+}
+
+/// The location of a (possibly fractional) pixel in the current image, at the full precision of the reference.
+pub fn pixel_to_location(renderer: &FractalRenderer, i: f64, j: f64) -> (String, String) {
+    let cos_rotate = renderer.rotate.cos();
+    let sin_rotate = renderer.rotate.sin();
+
+    let delta_pixel =  4.0 / ((renderer.image_height - 1) as f64 * renderer.zoom.mantissa);
+    let delta_top_left = get_delta_top_left(delta_pixel, renderer.image_width, renderer.image_height, cos_rotate, sin_rotate);
+
+    let element = ComplexFixed::new(
+        i * delta_pixel * cos_rotate - j * delta_pixel * sin_rotate + delta_top_left.re,
+        i * delta_pixel * sin_rotate + j * delta_pixel * cos_rotate + delta_top_left.im
+    );
+
+    let element = ComplexExtended::new(element, -renderer.zoom.exponent);
+
+    let mut location = renderer.center_reference.c.clone();
+
+    let precision = location.real().prec();
+
+    let temp = FloatArbitrary::with_val(precision, element.exponent).exp2();
+    let temp2 = FloatArbitrary::with_val(precision, element.mantissa.re);
+    let temp3 = FloatArbitrary::with_val(precision, element.mantissa.im);
+
+    *location.mut_real() += &temp2 * &temp;
+    *location.mut_imag() += &temp3 * &temp;
+
+    (location.real().to_string(), location.imag().to_string())
+}
+
+/// The location of the settings, with enough bits for the pixel spacing or for all of the digits that were given.
+pub fn location_arbitrary(settings: &Settings) -> Result<(FloatArbitrary, FloatArbitrary), String> {
+    let zoom = string_to_extended(&settings.zoom);
+
+    let precision = ((zoom.exponent.max(0) as usize + 64).max(4 * settings.real.len().max(settings.imag.len()))) as u32;
+
+    let real = FloatArbitrary::with_val(precision, FloatArbitrary::parse(&settings.real).map_err(|e| format!("real: {}", e))?);
+    let imag = FloatArbitrary::with_val(precision, FloatArbitrary::parse(&settings.imag).map_err(|e| format!("imag: {}", e))?);
+
+    Ok((real, imag))
+}
+
+/// The stored data of a single pixel. The distance and stripe values are only stored for the coloring methods that use them.
+pub fn sample_pixel(data_export: &DataExport, x: usize, y: usize) -> Option<PixelSample> {
+    if x >= data_export.image_width || y >= data_export.image_height {
+        return None;
+    }
+
+    let k = y * data_export.image_width + x;
+
+    let iterations = *data_export.iterations.get(k)?;
+
+    // Smooth values above one are not valid
+    let smooth = match data_export.smooth.get(k) {
+        Some(&smooth) if smooth <= 1.0 => smooth,
+        _ => 0.0
+    };
+
+    Some(PixelSample {
+        iterations,
+        smooth,
+        distance: data_export.distance.get(k).copied().unwrap_or(0.0),
+        stripe: data_export.stripe.get(k).copied().unwrap_or(0.0),
+        glitched: data_export.glitched.get(k).copied().unwrap_or(false),
+    })
+}
+
+/// The RGB values of the pixels surrounding (x, y). Pixels outside of the image are black.
+pub fn sample_loupe(data_export: &DataExport, x: usize, y: usize) -> Vec<u8> {
+    let half = (LOUPE_SIZE / 2) as i64;
+    let mut output = vec![0u8; 3 * LOUPE_SIZE * LOUPE_SIZE];
+
+    for j in 0..LOUPE_SIZE {
+        let source_y = y as i64 + j as i64 - half;
+
+        if source_y < 0 || source_y >= data_export.image_height as i64 {
+            continue;
+        }
+
+        for i in 0..LOUPE_SIZE {
+            let source_x = x as i64 + i as i64 - half;
+
+            if source_x < 0 || source_x >= data_export.image_width as i64 {
+                continue;
+            }
+
+            let source = 3 * (source_y as usize * data_export.image_width + source_x as usize);
+            let target = 3 * (j * LOUPE_SIZE + i);
+
+            if let Some(rgb) = data_export.buffer.get(source..(source + 3)) {
+                output[target..(target + 3)].copy_from_slice(rgb);
+            }
+        }
+    }
+
+    output
+}
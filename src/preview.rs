@@ -0,0 +1,56 @@
+use rust_fractal::renderer::FractalRenderer;
+
+// The preview is rendered at this fraction of the width and height of the image
+pub const PREVIEW_SCALE: usize = 8;
+
+// The preview uses this fraction of the iteration limit
+const PREVIEW_ITERATION_DIVISOR: usize = 4;
+
+// Images smaller than this in either direction are quick enough that no preview is rendered
+const PREVIEW_MINIMUM_SIZE: usize = 32;
+
+/// A low resolution render of a new location, shown until the full render draws over it.
+#[derive(Clone, Debug)]
+pub struct RenderPreview {
+    pub job: usize,
+    pub width: usize,
+    pub height: usize,
+    pub buffer: Vec<u8>,
+}
+
+/// The size of the preview of an image, or `None` if the image is too small to need one.
+pub fn preview_size(image_width: usize, image_height: usize) -> Option<(usize, usize)> {
+    let width = image_width / PREVIEW_SCALE;
+    let height = image_height / PREVIEW_SCALE;
+
+    if width < PREVIEW_MINIMUM_SIZE || height < PREVIEW_MINIMUM_SIZE {
+        return None;
+    }
+
+    Some((width, height))
+}
+
+/// The size of the render that calculates the reference and series approximation before the preview. This
+/// is the same fraction of the preview as the preview is of the image, so that few pixels are iterated to the
+/// full iteration limit.
+pub fn reference_size(preview_width: usize, preview_height: usize) -> (usize, usize) {
+    ((preview_width / PREVIEW_SCALE).max(2), (preview_height / PREVIEW_SCALE).max(2))
+}
+
+/// The iteration limit of the preview, which uses the reference of the full render.
+pub fn preview_iterations(maximum_iteration: usize) -> usize {
+    (maximum_iteration / PREVIEW_ITERATION_DIVISOR).max(1)
+}
+
+/// The image of a renderer that has just rendered at the preview size. The reference and series
+/// approximation are left in the renderer, so that the full image can be iterated with them.
+pub fn take_preview(job: usize, renderer: &FractalRenderer) -> RenderPreview {
+    let data_export = renderer.data_export.lock();
+
+    RenderPreview {
+        job,
+        width: data_export.image_width,
+        height: data_export.image_height,
+        buffer: data_export.buffer.clone(),
+    }
+}
@@ -0,0 +1,226 @@
+use std::time::Instant;
+
+use druid::Data;
+
+#[derive(Data, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RenderStage {
+    Complete,
+    Reference,
+    SeriesApproximation,
+    SeriesValidation,
+    Iteration,
+    Correction,
+}
+
+impl RenderStage {
+    /// The stages of a render, in the order they are run.
+    pub const ACTIVE: [RenderStage; 5] = [
+        RenderStage::Reference,
+        RenderStage::SeriesApproximation,
+        RenderStage::SeriesValidation,
+        RenderStage::Iteration,
+        RenderStage::Correction,
+    ];
+
+    pub fn index(&self) -> Option<usize> {
+        RenderStage::ACTIVE.iter().position(|stage| stage == self)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RenderStage::Complete => "Complete",
+            RenderStage::Reference => "Reference",
+            RenderStage::SeriesApproximation => "Approximation",
+            RenderStage::SeriesValidation => "Validation",
+            RenderStage::Iteration => "Iteration",
+            RenderStage::Correction => "Correction",
+        }
+    }
+
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            RenderStage::Complete => "END",
+            RenderStage::Reference => "REF",
+            RenderStage::SeriesApproximation => "SA",
+            RenderStage::SeriesValidation => "VAL",
+            RenderStage::Iteration => "IT",
+            RenderStage::Correction => "COR",
+        }
+    }
+}
+
+/// The progress of a render, sent from the render thread to the widget.
+#[derive(Data, Clone, PartialEq, Debug)]
+pub struct RenderProgress {
+    pub job: usize,
+    pub stage: RenderStage,
+    /// Progress through the current stage, between 0 and 1
+    pub progress: f64,
+    /// Total time elapsed in milliseconds
+    pub time: usize,
+    /// Time spent in each of the active stages in milliseconds
+    pub stage_times: [usize; 5],
+    /// Estimated time remaining in the current stage in milliseconds
+    pub remaining: Option<usize>,
+    /// Estimated time remaining in the whole render in milliseconds, where the stages that have not
+    /// started are expected to take as long as they did in the previous render
+    pub remaining_total: Option<usize>,
+    pub min_valid_iteration: usize,
+    pub max_valid_iteration: usize,
+    pub reference_count: usize,
+}
+
+impl RenderProgress {
+    pub fn new(job: usize) -> Self {
+        RenderProgress {
+            job,
+            stage: RenderStage::Reference,
+            progress: 0.0,
+            time: 0,
+            stage_times: [0; 5],
+            remaining: None,
+            remaining_total: None,
+            min_valid_iteration: 1,
+            max_valid_iteration: 1,
+            reference_count: 1,
+        }
+    }
+
+    /// How much of the given stage has been completed, used to draw one segment per stage.
+    pub fn stage_fraction(&self, stage: RenderStage) -> f64 {
+        match (self.stage.index(), stage.index()) {
+            (None, _) => 1.0,
+            (Some(current), Some(index)) if index < current => 1.0,
+            (Some(current), Some(index)) if index == current => self.progress.max(0.0).min(1.0),
+            _ => 0.0,
+        }
+    }
+}
+
+/// Keeps track of the timing of each stage and estimates the time remaining from the observed rate of progress,
+/// and from the stage times of the previous render for the stages that have not started.
+pub struct ProgressTracker {
+    job: usize,
+    start: Instant,
+    stage: RenderStage,
+    stage_start: Instant,
+    stage_start_progress: f64,
+    stage_times: [usize; 5],
+    previous_stage_times: [usize; 5],
+}
+
+impl ProgressTracker {
+    pub fn new(job: usize, previous_stage_times: [usize; 5]) -> Self {
+        let start = Instant::now();
+
+        ProgressTracker {
+            job,
+            start,
+            stage: RenderStage::Reference,
+            stage_start: start,
+            stage_start_progress: 0.0,
+            stage_times: [0; 5],
+            previous_stage_times,
+        }
+    }
+
+    /// The time spent in each of the active stages so far in milliseconds.
+    pub fn stage_times(&self) -> [usize; 5] {
+        self.stage_times
+    }
+
+    pub fn update(&mut self, stage: RenderStage, progress: f64, min_valid_iteration: usize, max_valid_iteration: usize, reference_count: usize) -> RenderProgress {
+        if stage != self.stage {
+            self.finish_stage();
+
+            self.stage = stage;
+            self.stage_start = Instant::now();
+            self.stage_start_progress = progress;
+        }
+
+        if let Some(index) = stage.index() {
+            self.stage_times[index] = self.stage_start.elapsed().as_millis() as usize;
+        }
+
+        // Only estimate once there has been enough progress to measure a rate
+        let stage_elapsed = self.stage_start.elapsed().as_secs_f64();
+        let stage_progress = progress - self.stage_start_progress;
+
+        let remaining = if stage_progress > 0.01 && stage_elapsed > 0.5 {
+            Some((1000.0 * stage_elapsed * (1.0 - progress).max(0.0) / stage_progress) as usize)
+        } else {
+            None
+        };
+
+        let remaining_total = stage.index().and_then(|index| {
+            // Until there is a rate, the stage is expected to take as long as it did before
+            let stage_remaining = remaining.or_else(|| match self.previous_stage_times[index] {
+                0 => None,
+                previous => Some(previous.saturating_sub(self.stage_times[index]))
+            })?;
+
+            Some(stage_remaining + self.previous_stage_times[(index + 1)..].iter().sum::<usize>())
+        });
+
+        RenderProgress {
+            job: self.job,
+            stage,
+            progress,
+            time: self.start.elapsed().as_millis() as usize,
+            stage_times: self.stage_times,
+            remaining,
+            remaining_total,
+            min_valid_iteration,
+            max_valid_iteration,
+            reference_count,
+        }
+    }
+
+    /// The final progress of the render, using the time measured by the renderer.
+    pub fn complete(&mut self, time: usize, min_valid_iteration: usize, max_valid_iteration: usize, reference_count: usize) -> RenderProgress {
+        self.finish_stage();
+        self.stage = RenderStage::Complete;
+
+        RenderProgress {
+            job: self.job,
+            stage: RenderStage::Complete,
+            progress: 1.0,
+            time,
+            stage_times: self.stage_times,
+            remaining: None,
+            remaining_total: None,
+            min_valid_iteration,
+            max_valid_iteration,
+            reference_count,
+        }
+    }
+
+    fn finish_stage(&mut self) {
+        if let Some(index) = self.stage.index() {
+            self.stage_times[index] = self.stage_start.elapsed().as_millis() as usize;
+        }
+    }
+}
+
+/// Formats a duration in milliseconds as h:mm:ss.mmm
+pub fn format_time(time: usize) -> String {
+    let ms = time % 1000;
+    let s = time / 1000;
+    let m = s / 60;
+    let h = m / 60;
+
+    format!("{}:{:0>2}:{:0>2}.{:0>3}", h, m % 60, s % 60, ms)
+}
+
+/// Formats a duration in milliseconds in at most six characters
+pub fn format_time_short(time: usize) -> String {
+    let s = time / 1000;
+
+    if s < 60 {
+        format!("{:.1}s", time as f64 / 1000.0)
+    } else if s < 3600 {
+        format!("{}m{:0>2}s", s / 60, s % 60)
+    } else {
+        format!("{}h{:0>2}m", s / 3600, (s / 60) % 60)
+    }
+}
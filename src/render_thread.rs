@@ -1,230 +1,504 @@
 use std::sync::mpsc;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::time::{Instant, Duration};
+use std::time::Duration;
+use std::path::{Path, PathBuf};
 
 use rust_fractal::{renderer::FractalRenderer, util::ComplexExtended};
-use rust_fractal::util::{FloatArbitrary, linear_interpolation_between_zoom, extended_to_string_long};
+use rust_fractal::util::{FloatArbitrary, linear_interpolation_between_zoom, extended_to_string_long, string_to_extended};
 use rust_fractal::math::{get_nucleus, get_nucleus_position};
 
-use druid::Target;
+use druid::{ExtEventSink, Target};
 use std::sync::Arc;
 use parking_lot::Mutex;
-use config::Config;
 use std::thread;
 
 use crate::commands::*;
+use crate::progress::{ProgressTracker, RenderProgress, RenderStage};
+use crate::notifications::Notification;
+use crate::palette::Palette;
+use crate::settings::Settings;
+use crate::export::{ExportOptions, render_export};
+use crate::headless::load_location_settings;
+use crate::pixel::location_arbitrary;
+use crate::preview::{preview_size, reference_size, preview_iterations, take_preview};
+use crate::formats::{apply_palette, is_png_file, location_text_chunks, write_png_text};
 
 
 pub fn testing_renderer(
-    event_sink: druid::ExtEventSink, 
-    reciever: mpsc::Receiver<usize>, 
-    thread_settings: Arc<Mutex<Config>>, 
+    event_sink: ExtEventSink, 
+    reciever: mpsc::Receiver<ThreadCommand>, 
     thread_renderer: Arc<Mutex<FractalRenderer>>, 
     thread_stop_flag: Arc<AtomicBool>,
     thread_repeat_flag: Arc<AtomicBool>) {
+    // The stage times of the last render that was not stopped, used to estimate the time remaining
+    let mut previous_stage_times = [0; 5];
+
     loop {
         let stop_flag = thread_stop_flag.clone();
         let repeat_flag = thread_repeat_flag.clone();
 
         if let Ok(command) = reciever.recv() {
             match command {
-                THREAD_RESET_RENDERER_FAST | THREAD_RESET_RENDERER_FULL => {
-                    let mut renderer = thread_renderer.lock();
-
-                    let thread_counter_1 = renderer.progress.reference.clone();
-                    let thread_counter_2 = renderer.progress.series_approximation.clone();
-                    let thread_counter_3 = renderer.progress.reference_maximum.clone();
-                    let thread_counter_4 = renderer.progress.series_validation.clone();
-                    let thread_counter_5 = renderer.progress.iteration.clone();
-                    let thread_counter_6 = renderer.progress.glitched_maximum.clone();
-                    let thread_counter_7 = renderer.progress.min_series_approximation.clone();
-                    let thread_counter_8 = renderer.progress.max_series_approximation.clone();
-                    let thread_counter_9 = renderer.progress.reference_count.clone();
-
-                    if command == THREAD_RESET_RENDERER_FULL {
-                        renderer.regenerate_from_settings(thread_settings.lock().clone());
-                    }
-
-                    let total_pixels = renderer.total_pixels as f64;
-
-                    let repaint_frequency = (renderer.total_pixels / 200000).max(1);
-
-                    let (tx, rx) = mpsc::channel();
-
-                    let test = event_sink.clone();
-
-                    thread::spawn(move || {
-                        let start = Instant::now();
-
-                        let mut index = 0;
-                        let mut stage = 1usize;
-
-                        loop {
-                            match rx.try_recv() {
-                                Ok(_) => {
-                                    break;
-                                },
-                                Err(_) => {
-                                    let series_validation_progress = thread_counter_4.load(Ordering::Relaxed);
-
-                                    let mut progress = 0.0;
-                                    
-                                    // Less than two means that the series validation has not completed
-                                    if series_validation_progress < 2 {
-                                        let series_approximation_amount = thread_counter_2.load(Ordering::Relaxed);
-
-                                        let reference_progress = thread_counter_1.load(Ordering::Relaxed) as f64;
-                                        let series_approximation_progress = series_approximation_amount as f64;
-                                        let reference_maximum = thread_counter_3.load(Ordering::Relaxed) as f64;
-
-                                        if series_approximation_amount == 0 {
-                                            progress = reference_progress / reference_maximum
-                                        } else {
-                                            stage = if series_approximation_progress / reference_maximum >= 1.0 {
-                                                3
-                                            } else {
-                                                2
-                                            };
-
-                                            progress += 0.9 * series_approximation_progress / reference_maximum;
-                                            progress += 0.1 * series_validation_progress as f64 / 2.0;
-                                        }
-                                    } else {
-                                        let glitched_amount = thread_counter_6.load(Ordering::Relaxed);
-
-                                        if glitched_amount != 0 {
-                                            let complete_amount = total_pixels as f64 - glitched_amount as f64;
-
-                                            stage = 5;
-                                            progress = (thread_counter_5.load(Ordering::Relaxed) as f64 - complete_amount) / glitched_amount as f64
-                                        } else {
-                                            stage = 4;
-                                            progress = thread_counter_5.load(Ordering::Relaxed) as f64 / total_pixels
-                                        }
-                                    };
-
-                                    let time = start.elapsed().as_millis() as usize;
-                                    let min_valid_iteration = thread_counter_7.load(Ordering::Relaxed);
-                                    let max_valid_iteration = thread_counter_8.load(Ordering::Relaxed);
-                                    let reference_count = thread_counter_9.load(Ordering::Relaxed);
-
-                                    test.submit_command(UPDATE_RENDERING_PROGRESS, (stage, progress, time, min_valid_iteration, max_valid_iteration, reference_count), Target::Auto).unwrap();
-                                }
-                            };
-
-                            if stage > 3 {
-                                index += 1;
-                                if index % repaint_frequency == 0 {
-                                    test.submit_command(REPAINT, (), Target::Auto).unwrap();
-                                    index = 0;
-                                }
-                            }
-                            
-                            thread::sleep(Duration::from_millis(20));
-                        };
-                    });
-                    
-                    if command == THREAD_RESET_RENDERER_FULL {
-                        renderer.render_frame(0, String::from(""), stop_flag);
-                    } else {
-                        renderer.render_frame(1, String::from(""), stop_flag);
-                    }
+                ThreadCommand::ResetRendererFull { job, settings, frame, preview } => {
+                    reset_renderer(&event_sink, &thread_renderer, job, settings, true, frame, preview, &mut previous_stage_times, stop_flag, repeat_flag);
+                }
+                ThreadCommand::ResetRendererFast { job, settings, frame } => {
+                    reset_renderer(&event_sink, &thread_renderer, job, settings, false, frame, false, &mut previous_stage_times, stop_flag, repeat_flag);
+                }
+                ThreadCommand::CalculateRoot { job, settings } => {
+                    calculate_root(&event_sink, &thread_renderer, job, settings, stop_flag);
+                }
+            }
+        }
+    }
+}
+
+// On a full reset the renderer is regenerated from the settings, otherwise the existing reference is used
+// with the size and iterations of the settings
+#[allow(clippy::too_many_arguments)]
+fn reset_renderer(
+    event_sink: &ExtEventSink,
+    thread_renderer: &Arc<Mutex<FractalRenderer>>,
+    job: usize,
+    mut settings: Settings,
+    full_reset: bool,
+    frame: Option<(usize, PathBuf)>,
+    preview: bool,
+    previous_stage_times: &mut [usize; 5],
+    stop_flag: Arc<AtomicBool>,
+    repeat_flag: Arc<AtomicBool>) {
+    let mut renderer = thread_renderer.lock();
+
+    // With a preview, the reference and series approximation are calculated by a small render, and then
+    // reused for the preview at fewer iterations and for the full image
+    let full_size = (settings.image_width, settings.image_height);
+    let preview_dimensions = Some(full_size).filter(|_| full_reset && preview).and_then(|(width, height)| preview_size(width, height));
+
+    if full_reset {
+        if let Some((width, height)) = preview_dimensions {
+            let (width, height) = reference_size(width, height);
+
+            settings.image_width = width;
+            settings.image_height = height;
+        }
 
-                    tx.send(()).unwrap();
+        renderer.regenerate_from_settings(settings.to_config());
+    } else {
+        if (renderer.image_width, renderer.image_height) != full_size {
+            set_renderer_size(&mut renderer, full_size.0, full_size.1);
+        }
 
-                    event_sink.submit_command(UPDATE_RENDERING_PROGRESS, (0, 1.0, renderer.render_time as usize, renderer.series_approximation.min_valid_iteration, renderer.series_approximation.max_valid_iteration, renderer.progress.reference_count.load(Ordering::SeqCst)), Target::Auto).unwrap();
-                    event_sink.submit_command(REPAINT, (), Target::Auto).unwrap();
+        renderer.maximum_iteration = settings.iterations;
+        renderer.data_type = settings.data_type();
+    }
 
-                    if command == THREAD_RESET_RENDERER_FAST {
-                        if (renderer.zoom.to_float() > 0.5) && repeat_flag.load(Ordering::SeqCst) {
-                            let zoom_out_factor = 1.0 / renderer.zoom_scale_factor;
-                            drop(renderer);
+    let maximum_iteration = renderer.maximum_iteration;
 
-                            // This is the delay between frames of zoom animations
-                            thread::sleep(Duration::from_millis(100));
+    let total_pixels = match preview_dimensions {
+        Some(_) => image_pixels(&renderer, full_size.0, full_size.1),
+        None => renderer.total_pixels
+    };
 
-                            event_sink.submit_command(MULTIPLY_ZOOM, zoom_out_factor, Target::Auto).unwrap();
-                        } else {
-                            repeat_flag.store(false, Ordering::SeqCst);
-                        };
-                    }
+    let repaint_frequency = (total_pixels / 200000).max(1);
+
+    let previewing = Arc::new(AtomicBool::new(preview_dimensions.is_some()));
+    let poller_previewing = previewing.clone();
+
+    let test = event_sink.clone();
+    let mut index = 0;
+
+    let (tx, poller) = spawn_progress_poller(&renderer, job, total_pixels, *previous_stage_times, move |render_progress| {
+        let iterating = matches!(render_progress.stage, RenderStage::Iteration | RenderStage::Correction);
+
+        // The preview is sent in one piece once it is complete, so its iteration is not shown
+        if iterating && poller_previewing.load(Ordering::SeqCst) {
+            return;
+        }
+
+        test.submit_command(UPDATE_RENDERING_PROGRESS, render_progress.clone(), Target::Auto).unwrap();
+
+        if iterating {
+            index += 1;
+            if index % repaint_frequency == 0 {
+                test.submit_command(REPAINT, job, Target::Auto).unwrap();
+                index = 0;
+            }
+        }
+    });
+    
+    if full_reset {
+        renderer.render_frame(0, String::from(""), stop_flag.clone());
+    } else {
+        renderer.render_frame(1, String::from(""), stop_flag.clone());
+    }
+
+    if let Some((preview_width, preview_height)) = preview_dimensions {
+        let (width, height) = full_size;
+
+        if !stop_flag.load(Ordering::SeqCst) {
+            set_renderer_size(&mut renderer, preview_width, preview_height);
+
+            renderer.maximum_iteration = preview_iterations(maximum_iteration);
+            renderer.data_export.lock().maximum_iteration = renderer.maximum_iteration;
+
+            renderer.render_frame(1, String::from(""), stop_flag.clone());
+        }
+
+        if !stop_flag.load(Ordering::SeqCst) {
+            event_sink.submit_command(PREVIEW_READY, take_preview(job, &renderer), Target::Auto).unwrap();
+        }
+
+        // The renderer always goes back to the full size and iterations, even if it was stopped during the preview
+        set_renderer_size(&mut renderer, width, height);
+
+        renderer.maximum_iteration = maximum_iteration;
+        renderer.data_export.lock().maximum_iteration = maximum_iteration;
+
+        previewing.store(false, Ordering::SeqCst);
+
+        if !stop_flag.load(Ordering::SeqCst) {
+            renderer.render_frame(1, String::from(""), stop_flag.clone());
+        }
+    }
+
+    tx.send(()).unwrap();
+
+    // Wait for the poller so that the final progress is always the last one sent
+    let mut tracker = poller.join().unwrap();
+
+    event_sink.submit_command(UPDATE_RENDERING_PROGRESS, tracker.complete(renderer.render_time as usize, renderer.series_approximation.min_valid_iteration, renderer.series_approximation.max_valid_iteration, renderer.progress.reference_count.load(Ordering::SeqCst)), Target::Auto).unwrap();
+    event_sink.submit_command(REPAINT, job, Target::Auto).unwrap();
+
+    let stopped = stop_flag.load(Ordering::SeqCst);
+
+    // A render using the existing reference only replaces the times of the stages it ran
+    if !stopped {
+        for (previous, time) in previous_stage_times.iter_mut().zip(tracker.stage_times().iter()) {
+            if full_reset || *time > 0 {
+                *previous = *time;
+            }
+        }
+    }
+
+    // Frames of a sequence are only saved if they were not interrupted
+    if let Some((index, path)) = &frame {
+        if !stopped {
+            match path.to_str() {
+                Some(output) if path.parent().map_or(true, |parent| parent.exists()) => {
+                    renderer.data_export.lock().save_colour(output);
+                    event_sink.submit_command(SEQUENCE_FRAME_SAVED, *index, Target::Auto).unwrap();
+                },
+                _ => {
+                    let notification = Notification::error(format!("Error saving frame {} to {}", index, path.display()));
+                    event_sink.submit_command(NOTIFY, notification, Target::Auto).unwrap();
                 }
-                THREAD_CALCULATE_ROOT => {
-                    let stop_flag = thread_stop_flag.clone();
+            }
+        }
+    }
 
-                    let mut renderer = thread_renderer.lock();
+    // A sequence may need a full render, for example when it is resumed
+    if !full_reset || frame.is_some() {
+        if (renderer.zoom.to_float() > 0.5) && repeat_flag.load(Ordering::SeqCst) {
+            let zoom_out_factor = 1.0 / renderer.zoom_scale_factor;
+            drop(renderer);
 
-                    renderer.find_period();
+            // This is the delay between frames of zoom animations
+            thread::sleep(Duration::from_millis(100));
 
-                    event_sink.submit_command(SET_PERIOD, renderer.period_finding.period, Target::Auto).unwrap();
-                    
-                    let mut box_center_arbitrary = renderer.center_reference.c.clone();
-                    let box_center = renderer.period_finding.box_center;
+            event_sink.submit_command(MULTIPLY_ZOOM, zoom_out_factor, Target::Auto).unwrap();
+        } else if (repeat_flag.swap(false, Ordering::SeqCst) || frame.is_some()) && !stopped {
+            event_sink.submit_command(ZOOM_OUT_COMPLETE, (), Target::Auto).unwrap();
+        };
+    }
+}
 
-                    let temp = FloatArbitrary::with_val(renderer.center_reference.c.real().prec(), box_center.exponent).exp2();
-            
-                    *box_center_arbitrary.mut_real() += temp.clone() * box_center.mantissa.re;
-                    *box_center_arbitrary.mut_imag() += temp.clone() * box_center.mantissa.im;
-
-                    let thread_counter_1 = Arc::new(AtomicUsize::new(0));
-                    let thread_counter_1_clone = thread_counter_1.clone();
-
-                    let thread_counter_2 = Arc::new(AtomicUsize::new(0));
-                    let thread_counter_2_clone = thread_counter_2.clone();
-
-                    let current_estimate_difference_1 = Arc::new(Mutex::new(ComplexExtended::new2(0.0, 0.0, -99999999)));
-                    let current_estimate_difference_2 = current_estimate_difference_1.clone();
-
-                    let (tx, rx) = mpsc::channel();
-
-                    let test = event_sink.clone();
-
-                    thread::spawn(move || {
-                        loop {
-                            match rx.try_recv() {
-                                Ok(_) => {
-                                    break;
-                                },
-                                Err(_) => {
-                                    // do some processing to get back to the original coordinates
-                                    test.submit_command(UPDATE_ROOT_PROGRESS, (thread_counter_1.load(Ordering::Relaxed), thread_counter_2.load(Ordering::Relaxed), *current_estimate_difference_1.lock()), Target::Auto).unwrap();
-                                }
-                            }
-                            
-                            thread::sleep(Duration::from_millis(20));
-                        };
-                    });
-                    
-                    if let Some(nucleus) = get_nucleus(box_center_arbitrary, renderer.period_finding.period, thread_counter_1_clone, thread_counter_2_clone, stop_flag, current_estimate_difference_2) {
-                        let nucleus_position = get_nucleus_position(nucleus.clone(), renderer.period_finding.period);
+/// Changes the size of the image without calculating a new reference. The buffers are resized in the next render.
+pub fn set_renderer_size(renderer: &mut FractalRenderer, width: usize, height: usize) {
+    renderer.image_width = width;
+    renderer.image_height = height;
+
+    renderer.total_pixels = image_pixels(renderer, width, height);
+}
+
+/// Moves the renderer to the location and size of the settings without calculating a new reference, so that
+/// pixels map to the right coordinates in an image that the renderer did not draw. The next render must
+/// regenerate the renderer.
+pub fn set_renderer_location(renderer: &mut FractalRenderer, settings: &Settings) -> Result<(), String> {
+    let (real, imag) = location_arbitrary(settings)?;
+
+    *renderer.center_reference.c.mut_real() = real;
+    *renderer.center_reference.c.mut_imag() = imag;
+
+    renderer.zoom = string_to_extended(&settings.zoom);
+    renderer.rotate = settings.rotate.to_radians();
+
+    set_renderer_size(renderer, settings.image_width, settings.image_height);
+
+    Ok(())
+}
+
+// The number of pixels that are iterated in an image of this size, as a removed centre is not iterated
+fn image_pixels(renderer: &FractalRenderer, width: usize, height: usize) -> usize {
+    let mut total_pixels = width * height;
+
+    if renderer.remove_centre {
+        let temp = 1.0 / renderer.zoom_scale_factor;
+
+        // Add one to avoid rescaling artifacts
+        let val1 = (width as f64 * temp).ceil() as usize - 1;
+        let val2 = (height as f64 * temp).ceil() as usize - 1;
+
+        total_pixels -= val1 * val2;
+    }
+
+    total_pixels
+}
+
+// Reports the progress of the renderer until a message is sent on the returned channel. The tracker
+// is returned when the poller finishes so that the final progress can be sent. The iteration progress
+// is measured against the given number of pixels, and the time remaining is estimated with the stage times
+// of a previous render.
+fn spawn_progress_poller(
+    renderer: &FractalRenderer,
+    job: usize,
+    total_pixels: usize,
+    previous_stage_times: [usize; 5],
+    mut report: impl FnMut(&RenderProgress) + Send + 'static) -> (mpsc::Sender<()>, thread::JoinHandle<ProgressTracker>) {
+    let thread_counter_1 = renderer.progress.reference.clone();
+    let thread_counter_2 = renderer.progress.series_approximation.clone();
+    let thread_counter_3 = renderer.progress.reference_maximum.clone();
+    let thread_counter_4 = renderer.progress.series_validation.clone();
+    let thread_counter_5 = renderer.progress.iteration.clone();
+    let thread_counter_6 = renderer.progress.glitched_maximum.clone();
+    let thread_counter_7 = renderer.progress.min_series_approximation.clone();
+    let thread_counter_8 = renderer.progress.max_series_approximation.clone();
+    let thread_counter_9 = renderer.progress.reference_count.clone();
+
+    let total_pixels = total_pixels as f64;
+
+    let (tx, rx) = mpsc::channel();
+
+    let poller = thread::spawn(move || {
+        let mut tracker = ProgressTracker::new(job, previous_stage_times);
+
+        let mut stage = RenderStage::Reference;
+
+        loop {
+            match rx.try_recv() {
+                Ok(_) => {
+                    break;
+                },
+                Err(_) => {
+                    let series_validation_progress = thread_counter_4.load(Ordering::Relaxed);
+
+                    let progress;
                     
-                        let new_zoom = linear_interpolation_between_zoom(renderer.zoom, nucleus_position.0, renderer.root_zoom_factor);
-    
-                        drop(renderer);
-    
-                        let mut settings = thread_settings.lock();
-    
-                        settings.set("real", nucleus.real().to_string()).unwrap();
-                        settings.set("imag", nucleus.imag().to_string()).unwrap();
-                        settings.set("zoom", extended_to_string_long(new_zoom)).unwrap();
-    
-                        drop(settings);
-    
-                        event_sink.submit_command(ROOT_FINDING_COMPLETE, Some(nucleus_position.0), Target::Auto).unwrap();
-    
-                        // this currently updates the data fields
-                        event_sink.submit_command(REVERT_LOCATION, (), Target::Auto).unwrap();
-                        event_sink.submit_command(RESET_RENDERER_FULL, (), Target::Auto).unwrap();
+                    // Less than two means that the series validation has not completed
+                    if series_validation_progress < 2 {
+                        let series_approximation_amount = thread_counter_2.load(Ordering::Relaxed);
+
+                        let reference_progress = thread_counter_1.load(Ordering::Relaxed) as f64;
+                        let series_approximation_progress = series_approximation_amount as f64;
+                        let reference_maximum = thread_counter_3.load(Ordering::Relaxed) as f64;
+
+                        if series_approximation_amount == 0 {
+                            progress = reference_progress / reference_maximum
+                        } else if series_approximation_progress / reference_maximum >= 1.0 {
+                            stage = RenderStage::SeriesValidation;
+                            progress = series_validation_progress as f64 / 2.0;
+                        } else {
+                            stage = RenderStage::SeriesApproximation;
+                            progress = series_approximation_progress / reference_maximum;
+                        }
                     } else {
-                        event_sink.submit_command(ROOT_FINDING_COMPLETE, None, Target::Auto).unwrap();
-                    }
+                        let glitched_amount = thread_counter_6.load(Ordering::Relaxed);
+
+                        if glitched_amount != 0 {
+                            let complete_amount = total_pixels as f64 - glitched_amount as f64;
+
+                            stage = RenderStage::Correction;
+                            progress = (thread_counter_5.load(Ordering::Relaxed) as f64 - complete_amount) / glitched_amount as f64
+                        } else {
+                            stage = RenderStage::Iteration;
+                            progress = thread_counter_5.load(Ordering::Relaxed) as f64 / total_pixels
+                        }
+                    };
+
+                    let min_valid_iteration = thread_counter_7.load(Ordering::Relaxed);
+                    let max_valid_iteration = thread_counter_8.load(Ordering::Relaxed);
+                    let reference_count = thread_counter_9.load(Ordering::Relaxed);
 
-                    tx.send(()).unwrap();
+                    let render_progress = tracker.update(stage, progress, min_valid_iteration, max_valid_iteration, reference_count);
+
+                    report(&render_progress);
                 }
-                _ => {
-                    println!("thread_command: {}", command);
+            };
+            
+            thread::sleep(Duration::from_millis(20));
+        };
+
+        tracker
+    });
+
+    (tx, poller)
+}
+
+// Exports run on their own thread with their own stop flag, so that they do not hold up or get stopped with the preview
+pub fn export_thread(event_sink: ExtEventSink, reciever: mpsc::Receiver<ExportJob>, stop_flag: Arc<AtomicBool>) {
+    for job in reciever.iter() {
+        export(&event_sink, &job.settings, &job.palette, &job.options, stop_flag.clone());
+    }
+}
+
+// The stop flag is cleared when the export is sent, so that stopping it before it starts is not lost
+fn export(event_sink: &ExtEventSink, settings: &Settings, palette: &Palette, options: &ExportOptions, stop_flag: Arc<AtomicBool>) {
+    let result = render_export(settings, palette, options, stop_flag.clone(), |completed, total| {
+        event_sink.submit_command(EXPORT_PROGRESS, Some((completed, total)), Target::Auto).unwrap();
+    });
+
+    let notification = match result {
+        Ok(()) => Notification::info(format!("Exported {}", options.path.display())),
+        Err(_) if stop_flag.load(Ordering::SeqCst) => Notification::info(format!("Export of {} stopped", options.path.display())),
+        Err(e) => Notification::error(format!("Error exporting {}: {}", options.path.display(), e))
+    };
+
+    event_sink.submit_command(EXPORT_PROGRESS, None, Target::Auto).unwrap();
+    event_sink.submit_command(NOTIFY, notification, Target::Auto).unwrap();
+}
+
+// The batch queue runs on its own thread with its own stop flag, one job at a time
+pub fn batch_thread(event_sink: ExtEventSink, reciever: mpsc::Receiver<BatchRender>, stop_flag: Arc<AtomicBool>) {
+    for job in reciever.iter() {
+        let result = render_batch_job(&event_sink, job.id, &job.location, &job.output, stop_flag.clone());
+        event_sink.submit_command(BATCH_JOB_COMPLETE, (job.id, result), Target::Auto).unwrap();
+    }
+}
+
+// Renders a location file with a separate renderer and saves the image, in the same way as the headless mode.
+// The stop flag is cleared when the job is sent, and nothing is saved if the job is stopped.
+fn render_batch_job(event_sink: &ExtEventSink, id: usize, location: &Path, output: &Path, stop_flag: Arc<AtomicBool>) -> Result<(), String> {
+    let output_path = output.to_str().ok_or_else(|| format!("invalid output path {}", output.display()))?;
+
+    let (mut settings, palette, warnings) = load_location_settings(location)?;
+
+    for warning in warnings {
+        event_sink.submit_command(NOTIFY, Notification::warning(format!("{}: {}", location.display(), warning)), Target::Auto).unwrap();
+    }
+
+    settings.show_output = false;
+
+    let mut renderer = FractalRenderer::new(settings.to_config());
+
+    if let Some(palette) = &palette {
+        apply_palette(&mut renderer.data_export.lock(), &settings, palette);
+    }
+
+    let sink = event_sink.clone();
+
+    // Each batch location is different, so there are no previous stage times to estimate with
+    let (tx, poller) = spawn_progress_poller(&renderer, id, renderer.total_pixels, [0; 5], move |render_progress| {
+        sink.submit_command(BATCH_PROGRESS, (id, render_progress.clone()), Target::Auto).unwrap();
+    });
+
+    renderer.render_frame(0, String::from(""), stop_flag.clone());
+
+    tx.send(()).unwrap();
+    poller.join().unwrap();
+
+    if stop_flag.load(Ordering::SeqCst) {
+        return Err("stopped".to_string());
+    }
+
+    renderer.data_export.lock().save_colour(output_path);
+
+    // The location is stored in the image so that it can be opened again
+    if is_png_file(output) {
+        let palette = palette.unwrap_or_else(|| Palette::from_data_export(&renderer.data_export.lock()));
+        write_png_text(output, &location_text_chunks(&settings, &palette))?;
+    }
+
+    Ok(())
+}
+
+// The root is zoomed towards from the zoom of the settings, and is sent back with the job so that the
+// result of a root finding that has been replaced is ignored
+fn calculate_root(
+    event_sink: &ExtEventSink,
+    thread_renderer: &Arc<Mutex<FractalRenderer>>,
+    job: usize,
+    settings: Settings,
+    stop_flag: Arc<AtomicBool>) {
+    let mut renderer = thread_renderer.lock();
+
+    renderer.find_period();
+
+    event_sink.submit_command(SET_PERIOD, renderer.period_finding.period, Target::Auto).unwrap();
+    
+    let mut box_center_arbitrary = renderer.center_reference.c.clone();
+    let box_center = renderer.period_finding.box_center;
+
+    let temp = FloatArbitrary::with_val(renderer.center_reference.c.real().prec(), box_center.exponent).exp2();
+
+    *box_center_arbitrary.mut_real() += temp.clone() * box_center.mantissa.re;
+    *box_center_arbitrary.mut_imag() += temp.clone() * box_center.mantissa.im;
+
+    let thread_counter_1 = Arc::new(AtomicUsize::new(0));
+    let thread_counter_1_clone = thread_counter_1.clone();
+
+    let thread_counter_2 = Arc::new(AtomicUsize::new(0));
+    let thread_counter_2_clone = thread_counter_2.clone();
+
+    let current_estimate_difference_1 = Arc::new(Mutex::new(ComplexExtended::new2(0.0, 0.0, -99999999)));
+    let current_estimate_difference_2 = current_estimate_difference_1.clone();
+
+    let (tx, rx) = mpsc::channel();
+
+    let test = event_sink.clone();
+
+    thread::spawn(move || {
+        loop {
+            match rx.try_recv() {
+                Ok(_) => {
+                    break;
+                },
+                Err(_) => {
+                    // do some processing to get back to the original coordinates
+                    test.submit_command(UPDATE_ROOT_PROGRESS, (job, thread_counter_1.load(Ordering::Relaxed), thread_counter_2.load(Ordering::Relaxed), *current_estimate_difference_1.lock()), Target::Auto).unwrap();
                 }
             }
-        }
+            
+            thread::sleep(Duration::from_millis(20));
+        };
+    });
+    
+    if let Some(nucleus) = get_nucleus(box_center_arbitrary, renderer.period_finding.period, thread_counter_1_clone, thread_counter_2_clone, stop_flag.clone(), current_estimate_difference_2) {
+        let nucleus_position = get_nucleus_position(nucleus.clone(), renderer.period_finding.period);
+    
+        let new_zoom = linear_interpolation_between_zoom(string_to_extended(&settings.zoom), nucleus_position.0, renderer.root_zoom_factor);
+
+        drop(renderer);
+
+        let root = FoundRoot {
+            real: nucleus.real().to_string(),
+            imag: nucleus.imag().to_string(),
+            zoom: extended_to_string_long(new_zoom),
+            root_zoom: nucleus_position.0,
+        };
+
+        event_sink.submit_command(ROOT_FINDING_COMPLETE, (job, Some(root)), Target::Auto).unwrap();
+    } else {
+        let period = renderer.period_finding.period;
+        drop(renderer);
+
+        let notification = if stop_flag.load(Ordering::SeqCst) {
+            Notification::info("Root finding stopped")
+        } else {
+            Notification::warning(format!("Root finding did not converge for period {}", period))
+        };
+
+        event_sink.submit_command(NOTIFY, notification, Target::Auto).unwrap();
+        event_sink.submit_command(ROOT_FINDING_COMPLETE, (job, None), Target::Auto).unwrap();
     }
-}
\ No newline at end of file
+
+    tx.send(()).unwrap();
+}
@@ -0,0 +1,249 @@
+use std::convert::TryFrom;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use config::{Config, File};
+use druid::Data;
+use rust_fractal::util::FloatExtended;
+
+use crate::palette::Palette;
+use crate::settings::{Settings, toml_string};
+
+// Written to the output directory so that an interrupted sequence can be resumed
+pub const MANIFEST_NAME: &str = "sequence.toml";
+
+#[derive(Data, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SequenceFormat {
+    Png,
+    Jpg,
+}
+
+impl SequenceFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SequenceFormat::Png => "png",
+            SequenceFormat::Jpg => "jpg",
+        }
+    }
+
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_ref() {
+            "png" => Some(SequenceFormat::Png),
+            "jpg" | "jpeg" => Some(SequenceFormat::Jpg),
+            _ => None
+        }
+    }
+}
+
+/// A zoom out sequence that saves each frame to a directory as it is rendered.
+pub struct ZoomSequence {
+    pub directory: PathBuf,
+    pub real: String,
+    pub imag: String,
+    /// The zoom of frame zero, before any frame offset is applied
+    pub zoom: String,
+    pub rotate: f64,
+    pub fractal_type: String,
+    pub fractal_power: i64,
+    /// The palette of the frames, which is `None` for manifests written before it was stored
+    pub palette: Option<Palette>,
+    pub palette_iteration_span: f64,
+    pub palette_offset: f64,
+    pub palette_cyclic: bool,
+    /// The iteration limit of the most recently completed frame
+    pub iterations: usize,
+    pub image_width: usize,
+    pub image_height: usize,
+    pub remove_centre: bool,
+    /// The file name of each frame, where `{frame}` is replaced with the frame number
+    pub template: String,
+    pub format: SequenceFormat,
+    pub zoom_scale_factor: f64,
+    /// The next frame to be rendered
+    pub frame: usize,
+    /// One past the last frame to be rendered, or None to continue until fully zoomed out
+    pub end_frame: Option<usize>,
+}
+
+impl ZoomSequence {
+    /// A new sequence starting from the location, formula and colouring in the settings.
+    pub fn new(directory: PathBuf, settings: &Settings, palette: &Palette, template: String, format: SequenceFormat, zoom_scale_factor: f64, frame_offset: usize, frames: usize) -> Self {
+        ZoomSequence {
+            directory,
+            real: settings.real.clone(),
+            imag: settings.imag.clone(),
+            zoom: settings.zoom.clone(),
+            rotate: settings.rotate,
+            fractal_type: settings.fractal_type.clone(),
+            fractal_power: settings.fractal_power,
+            palette: Some(palette.clone()),
+            palette_iteration_span: settings.palette_iteration_span,
+            palette_offset: settings.palette_offset,
+            palette_cyclic: settings.palette_cyclic,
+            iterations: settings.iterations,
+            image_width: settings.image_width,
+            image_height: settings.image_height,
+            remove_centre: settings.remove_centre,
+            template,
+            format,
+            zoom_scale_factor,
+            frame: frame_offset,
+            end_frame: if frames == 0 {
+                None
+            } else {
+                Some(frame_offset + frames)
+            },
+        }
+    }
+
+    pub fn frame_path(&self, frame: usize) -> PathBuf {
+        let name = if self.template.contains("{frame}") {
+            self.template.replace("{frame}", &format!("{:05}", frame))
+        } else {
+            format!("{}{:05}", self.template, frame)
+        };
+
+        self.directory.join(format!("{}.{}", name, self.format.extension()))
+    }
+
+    pub fn is_last(&self, frame: usize) -> bool {
+        matches!(self.end_frame, Some(end_frame) if frame + 1 >= end_frame)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        matches!(self.end_frame, Some(end_frame) if self.frame >= end_frame)
+    }
+
+    /// Records that all frames before `next_frame` have been saved.
+    pub fn write_manifest(&self, next_frame: usize) -> Result<(), String> {
+        let end_frame = match self.end_frame {
+            Some(end_frame) => end_frame as i64,
+            None => -1
+        };
+
+        let colouring = match &self.palette {
+            Some(palette) => format!(
+                "{}\npalette_iteration_span = {}\npalette_offset = {}\npalette_cyclic = {}\n\n",
+                palette.to_toml(),
+                self.palette_iteration_span,
+                self.palette_offset,
+                self.palette_cyclic),
+            None => String::new()
+        };
+
+        let output = format!(
+            "version = \"{}\"\n\nreal = \"{}\"\nimag = \"{}\"\nzoom = \"{}\"\nrotate = {}\niterations = {}\nzoom_scale = {}\nfractal_type = {}\nfractal_power = {}\n\n{}image_width = {}\nimage_height = {}\nremove_centre = {}\ntemplate = {}\nformat = \"{}\"\n\nnext_frame = {}\nend_frame = {}\n",
+            env!("CARGO_PKG_VERSION"),
+            self.real,
+            self.imag,
+            self.zoom,
+            self.rotate,
+            self.iterations,
+            self.zoom_scale_factor,
+            toml_string(&self.fractal_type),
+            self.fractal_power,
+            colouring,
+            self.image_width,
+            self.image_height,
+            self.remove_centre,
+            toml_string(&self.template),
+            self.format.extension(),
+            next_frame,
+            end_frame);
+
+        // The manifest is replaced in one step, so a crash while writing leaves the previous one intact
+        let manifest_path = self.directory.join(MANIFEST_NAME);
+        let temporary_path = self.directory.join(format!("{}.tmp", MANIFEST_NAME));
+
+        let write = || -> std::io::Result<()> {
+            let mut file = std::fs::File::create(&temporary_path)?;
+            file.write_all(output.as_bytes())?;
+            file.sync_all()
+        };
+
+        write().map_err(|e| e.to_string())?;
+        std::fs::rename(&temporary_path, &manifest_path).map_err(|e| e.to_string())
+    }
+
+    /// Loads a sequence from its manifest, continuing from the first frame that was not saved.
+    pub fn from_manifest(path: &Path) -> Result<Self, String> {
+        let mut manifest = Config::default();
+        manifest.merge(File::from(path)).map_err(|e| e.to_string())?;
+
+        let directory = path.parent().map(|parent| parent.to_path_buf()).unwrap_or_default();
+
+        let get_str = |key: &str| manifest.get_str(key).map_err(|e| format!("{}: {}", key, e));
+        let get_int = |key: &str| manifest.get_int(key).map_err(|e| format!("{}: {}", key, e));
+
+        let get_usize = |key: &str| get_int(key).and_then(|value| {
+            usize::try_from(value).map_err(|_| format!("{} must not be negative, found {}", key, value))
+        });
+
+        let format = get_str("format")?;
+        let format = SequenceFormat::from_extension(&format).ok_or_else(|| format!("unknown format {}", format))?;
+
+        let end_frame = get_int("end_frame")?;
+
+        let image_width = get_usize("image_width")?;
+        let image_height = get_usize("image_height")?;
+
+        if image_width == 0 || image_height == 0 {
+            return Err(format!("the image size {}x{} must be at least one pixel", image_width, image_height));
+        }
+
+        let zoom_scale_factor = manifest.get_float("zoom_scale").map_err(|e| format!("zoom_scale: {}", e))?;
+
+        // Each frame must zoom out, otherwise the sequence would never finish
+        if zoom_scale_factor.is_nan() || zoom_scale_factor <= 1.0 {
+            return Err(format!("zoom_scale must be greater than 1, found {}", zoom_scale_factor));
+        }
+
+        // Problems with the palette are errors, as the frames would not match the ones already saved
+        let mut warnings = Vec::new();
+        let palette = Palette::from_config(&manifest, &mut warnings);
+
+        if let Some(warning) = warnings.into_iter().next() {
+            return Err(warning);
+        }
+
+        Ok(ZoomSequence {
+            directory,
+            real: get_str("real")?,
+            imag: get_str("imag")?,
+            zoom: get_str("zoom")?,
+            rotate: manifest.get_float("rotate").unwrap_or(0.0),
+            // Sequences without a formula are of the quadratic Mandelbrot set, as for locations
+            fractal_type: manifest.get_str("fractal_type").unwrap_or_else(|_| "mandelbrot".to_string()),
+            fractal_power: manifest.get_int("fractal_power").unwrap_or(2),
+            palette,
+            palette_iteration_span: manifest.get_float("palette_iteration_span").unwrap_or(1.0),
+            palette_offset: manifest.get_float("palette_offset").unwrap_or(0.0),
+            palette_cyclic: manifest.get_bool("palette_cyclic").unwrap_or(true),
+            iterations: get_usize("iterations")?,
+            image_width,
+            image_height,
+            remove_centre: manifest.get_bool("remove_centre").unwrap_or(false),
+            template: get_str("template")?,
+            format,
+            zoom_scale_factor,
+            frame: get_usize("next_frame")?,
+            end_frame: if end_frame < 0 {
+                None
+            } else {
+                Some(end_frame as usize)
+            },
+        })
+    }
+}
+
+/// The zoom of a frame in a sequence starting at the given zoom, where each frame is zoomed out by the scale factor.
+pub fn zoom_for_frame(zoom: FloatExtended, zoom_scale_factor: f64, frame: usize) -> FloatExtended {
+    // Calculated in log2 so that long sequences do not underflow
+    let log2_zoom = zoom.mantissa.log2() + zoom.exponent as f64 - frame as f64 * zoom_scale_factor.log2();
+    let exponent = log2_zoom.floor();
+
+    let mut output = FloatExtended::new(2.0f64.powf(log2_zoom - exponent), exponent as i32);
+    output.reduce();
+
+    output
+}
@@ -0,0 +1,460 @@
+use std::path::Path;
+
+use config::{Config, ConfigError, File};
+
+use rust_fractal::util::data_export::DataType;
+
+use crate::palette::Palette;
+
+/// The type of a setting, along with the value used when it is missing.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SettingValue {
+    Str(&'static str),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+pub struct SettingDefinition {
+    pub key: &'static str,
+    pub default: SettingValue,
+    /// The inclusive range of valid values for numeric settings
+    pub range: Option<(f64, f64)>,
+}
+
+// Each setting is listed once, in `settings_schema!`, which generates the definitions in `SETTING_GROUPS`, the fields
+// of `Settings` and the code to read and write them
+macro_rules! settings_schema {
+    ($({ $($key:ident: $type:ty = $variant:ident($default:expr) $(in [$minimum:expr, $maximum:expr])?;)* })*) => {
+        /// Every setting used by the renderer and the GUI, in the groups they are written in. Adding a setting
+        /// to the schema is enough for it to be saved with the full configuration.
+        pub const SETTING_GROUPS: &[&[SettingDefinition]] = &[
+            $(&[
+                $(SettingDefinition {
+                    key: stringify!($key),
+                    default: SettingValue::$variant($default),
+                    range: settings_schema!(@range $($minimum, $maximum)?),
+                },)*
+            ],)*
+        ];
+
+        /// The validated settings used by the renderer and the GUI. Missing settings take the defaults in the schema.
+        #[derive(Clone, PartialEq, Debug)]
+        pub struct Settings {
+            $($(pub $key: $type,)*)*
+        }
+
+        impl Default for Settings {
+            fn default() -> Self {
+                Settings {
+                    $($($key: SettingType::from_default(SettingValue::$variant($default)),)*)*
+                }
+            }
+        }
+
+        impl Settings {
+            // Only the settings present in the configuration are changed
+            fn read_config(&mut self, config: &Config) -> Result<(), String> {
+                $($(if let Some(value) = SettingType::read(config, definition(stringify!($key)))? {
+                    self.$key = value;
+                })*)*
+
+                Ok(())
+            }
+
+            /// Writes every setting into the configuration passed to the renderer, so that none of them are missing.
+            pub fn write_to(&self, config: &mut Config) {
+                $($(SettingType::write(&self.$key, config, stringify!($key));)*)*
+
+                // Images are always saved by the GUI or the headless renderer, never by the renderer itself
+                config.set("export", "gui").unwrap();
+            }
+        }
+    };
+    (@range $minimum:expr, $maximum:expr) => { Some(($minimum, $maximum)) };
+    (@range) => { None };
+}
+
+settings_schema! {
+    {
+        real: String = Str("-0.75");
+        imag: String = Str("0.0");
+        zoom: String = Str("1E0");
+        iterations: usize = Int(1000) in [1.0, f64::INFINITY];
+        rotate: f64 = Float(0.0);
+    }
+    {
+        image_width: usize = Int(1024) in [2.0, 65536.0];
+        image_height: usize = Int(768) in [2.0, 65536.0];
+        fractal_type: String = Str("mandelbrot");
+        fractal_power: i64 = Int(3) in [2.0, 64.0];
+        approximation_order: usize = Int(16) in [4.0, 128.0];
+        glitch_percentage: f64 = Float(0.001) in [0.0, 100.0];
+        glitch_tolerance: f64 = Float(1.4e-6) in [0.0, 1.0];
+        probe_sampling: usize = Int(15) in [2.0, 128.0];
+        data_storage_interval: usize = Int(100) in [1.0, f64::INFINITY];
+        valid_iteration_frame_multiplier: f64 = Float(0.1) in [0.0, 1.0];
+        valid_iteration_probe_multiplier: f64 = Float(0.01) in [0.0, 1.0];
+        series_approximation_tiled: bool = Bool(true);
+        series_approximation_enabled: bool = Bool(false);
+        auto_adjust_iterations: bool = Bool(true);
+        remove_centre: bool = Bool(false);
+        display_glitches: bool = Bool(false);
+        jitter: bool = Bool(false);
+        jitter_factor: f64 = Float(0.2) in [0.0, 100.0];
+        show_output: bool = Bool(true);
+    }
+    {
+        frames: usize = Int(1) in [0.0, f64::INFINITY];
+        frame_offset: usize = Int(0) in [0.0, f64::INFINITY];
+        zoom_scale: f64 = Float(2.0) in [1.0, f64::INFINITY];
+    }
+    {
+        coloring_type: String = Str("smooth_iteration");
+        palette_iteration_span: f64 = Float(100.0) in [0.01, f64::INFINITY];
+        palette_offset: f64 = Float(0.0) in [0.0, 1.0];
+        palette_cyclic: bool = Bool(true);
+        stripe_scale: f64 = Float(1.0) in [0.0, 100.0];
+        distance_transition: f64 = Float(10.0) in [0.0, f64::INFINITY];
+        distance_color: bool = Bool(false);
+    }
+    {
+        lighting: bool = Bool(true);
+        lighting_direction: f64 = Float(30.0) in [0.0, 360.0];
+        lighting_azimuth: f64 = Float(35.0) in [0.0, 90.0];
+        lighting_opacity: f64 = Float(0.75) in [0.0, 1.0];
+        lighting_ambient: f64 = Float(0.4) in [0.0, 1.0];
+        lighting_diffuse: f64 = Float(0.5) in [0.0, 1.0];
+        lighting_specular: f64 = Float(0.5) in [0.0, 1.0];
+        lighting_shininess: i64 = Int(20) in [0.0, 1000.0];
+    }
+}
+
+/// A TOML basic string. Rust debug formatting is not used as its escapes, such as `\u{1b}`, are not valid TOML.
+pub fn toml_string(value: &str) -> String {
+    let mut output = String::with_capacity(value.len() + 2);
+
+    output.push('"');
+
+    for character in value.chars() {
+        match character {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            character if character.is_control() => output.push_str(&format!("\\u{:04X}", character as u32)),
+            character => output.push(character),
+        }
+    }
+
+    output.push('"');
+    output
+}
+
+/// The current value of a setting as a TOML value, or None if it is not set.
+fn format_setting(settings: &Config, definition: &SettingDefinition) -> Option<String> {
+    match definition.default {
+        SettingValue::Str(_) => settings.get_str(definition.key).ok().map(|value| toml_string(&value)),
+        SettingValue::Int(_) => settings.get_int(definition.key).ok().map(|value| value.to_string()),
+        // Debug formatting always includes a decimal point or exponent, so the value is read back as a float
+        SettingValue::Float(_) => settings.get_float(definition.key).ok().map(|value| format!("{:?}", value)),
+        SettingValue::Bool(_) => settings.get_bool(definition.key).ok().map(|value| value.to_string()),
+    }
+}
+
+/// Every setting in the schema with its current value, followed by the palette. Loading the output
+/// gives the same render.
+pub fn serialize_settings(settings: &Settings, palette: &Palette) -> String {
+    let settings = settings.to_config();
+    let mut groups = vec![format!("version = \"{}\"", env!("CARGO_PKG_VERSION"))];
+
+    for group in SETTING_GROUPS {
+        groups.push(group.iter()
+            .filter_map(|definition| format_setting(&settings, definition).map(|value| format!("{} = {}", definition.key, value)))
+            .collect::<Vec<String>>()
+            .join("\n"));
+    }
+
+    // The GUI does not export images itself, but the file should still render from the command line
+    groups.push("export = \"png\"".to_string());
+    groups.push(palette.to_toml());
+
+    groups.join("\n\n") + "\n"
+}
+
+fn definition(key: &str) -> &'static SettingDefinition {
+    SETTING_GROUPS.iter()
+        .flat_map(|group| group.iter())
+        .find(|definition| definition.key == key)
+        .unwrap_or_else(|| panic!("setting {} is not in the schema", key))
+}
+
+fn check_range(definition: &SettingDefinition, value: f64) -> Result<(), String> {
+    match definition.range {
+        Some((minimum, maximum)) if !(minimum..=maximum).contains(&value) => {
+            if maximum.is_infinite() {
+                Err(format!("{} must be at least {}, found {}", definition.key, minimum, value))
+            } else {
+                Err(format!("{} must be between {} and {}, found {}", definition.key, minimum, maximum, value))
+            }
+        },
+        _ => Ok(())
+    }
+}
+
+/// Limits a value from the GUI to the valid range of the setting.
+pub fn clamp_setting(key: &str, value: f64) -> f64 {
+    match definition(key).range {
+        Some((minimum, maximum)) => value.max(minimum).min(maximum),
+        None => value
+    }
+}
+
+// Missing settings are None, but settings of the wrong type are an error
+fn read<T>(result: Result<T, ConfigError>, key: &str) -> Result<Option<T>, String> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ConfigError::NotFound(_)) => Ok(None),
+        Err(e) => Err(format!("{}: {}", key, e)),
+    }
+}
+
+/// The types a setting can be stored as in `Settings`.
+trait SettingType: Sized {
+    fn from_default(default: SettingValue) -> Self;
+
+    fn read(config: &Config, definition: &SettingDefinition) -> Result<Option<Self>, String>;
+
+    fn write(&self, config: &mut Config, key: &str);
+}
+
+impl SettingType for String {
+    fn from_default(default: SettingValue) -> Self {
+        match default {
+            SettingValue::Str(default) => default.to_string(),
+            _ => panic!("the default of a string setting must be a string"),
+        }
+    }
+
+    fn read(config: &Config, definition: &SettingDefinition) -> Result<Option<Self>, String> {
+        read(config.get_str(definition.key), definition.key)
+    }
+
+    fn write(&self, config: &mut Config, key: &str) {
+        config.set(key, self.as_str()).unwrap();
+    }
+}
+
+impl SettingType for i64 {
+    fn from_default(default: SettingValue) -> Self {
+        match default {
+            SettingValue::Int(default) => default,
+            _ => panic!("the default of an integer setting must be an integer"),
+        }
+    }
+
+    fn read(config: &Config, definition: &SettingDefinition) -> Result<Option<Self>, String> {
+        let value = read(config.get_int(definition.key), definition.key)?;
+
+        if let Some(value) = value {
+            check_range(definition, value as f64)?;
+        }
+
+        Ok(value)
+    }
+
+    fn write(&self, config: &mut Config, key: &str) {
+        config.set(key, *self).unwrap();
+    }
+}
+
+impl SettingType for usize {
+    fn from_default(default: SettingValue) -> Self {
+        i64::from_default(default) as usize
+    }
+
+    fn read(config: &Config, definition: &SettingDefinition) -> Result<Option<Self>, String> {
+        match i64::read(config, definition)? {
+            Some(value) if value < 0 => Err(format!("{} must not be negative, found {}", definition.key, value)),
+            value => Ok(value.map(|value| value as usize))
+        }
+    }
+
+    fn write(&self, config: &mut Config, key: &str) {
+        config.set(key, *self as i64).unwrap();
+    }
+}
+
+impl SettingType for f64 {
+    fn from_default(default: SettingValue) -> Self {
+        match default {
+            SettingValue::Float(default) => default,
+            _ => panic!("the default of a float setting must be a float"),
+        }
+    }
+
+    fn read(config: &Config, definition: &SettingDefinition) -> Result<Option<Self>, String> {
+        let value = read(config.get_float(definition.key), definition.key)?;
+
+        if let Some(value) = value {
+            if !value.is_finite() {
+                return Err(format!("{} must be a finite number, found {}", definition.key, value));
+            }
+
+            check_range(definition, value)?;
+        }
+
+        Ok(value)
+    }
+
+    fn write(&self, config: &mut Config, key: &str) {
+        config.set(key, *self).unwrap();
+    }
+}
+
+impl SettingType for bool {
+    fn from_default(default: SettingValue) -> Self {
+        match default {
+            SettingValue::Bool(default) => default,
+            _ => panic!("the default of a boolean setting must be a boolean"),
+        }
+    }
+
+    fn read(config: &Config, definition: &SettingDefinition) -> Result<Option<Self>, String> {
+        read(config.get_bool(definition.key), definition.key)
+    }
+
+    fn write(&self, config: &mut Config, key: &str) {
+        config.set(key, *self).unwrap();
+    }
+}
+
+/// Checks the type and range of every setting in the schema that is present in a loaded file.
+pub fn validate_settings(config: &Config) -> Result<(), String> {
+    Settings::default().read_config(config)
+}
+
+// Splits a version such as 0.3.0 into its numbers
+fn parse_version(version: &str) -> Vec<u64> {
+    version.split('.').map(|part| part.trim().parse::<u64>().unwrap_or(0)).collect()
+}
+
+/// A warning if the configuration was saved by a newer version, which may have settings this one ignores.
+pub fn version_warning(config: &Config) -> Option<String> {
+    // Location files do not store a version
+    let version = config.get_str("version").ok()?;
+
+    if parse_version(&version) > parse_version(env!("CARGO_PKG_VERSION")) {
+        Some(format!("Configuration was saved by version {}, some settings may be ignored", version))
+    } else {
+        None
+    }
+}
+
+/// Brings files saved by other versions up to date with the current keys.
+pub fn migrate_settings(config: &mut Config) {
+    if config.get_str("version").is_err() {
+        return;
+    }
+
+    // Earlier versions saved the interpolated palette as RGB, without saying so
+    if config.get_array("palette").is_ok() && config.get_str("palette_channel_order").is_err() {
+        config.set("palette_channel_order", "rgb").unwrap();
+    }
+
+    if let Ok(rotate) = config.get_float("rotate") {
+        config.set("rotate", rotate.rem_euclid(360.0)).unwrap();
+    }
+}
+
+impl Settings {
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let mut settings = Settings::default();
+        settings.merge_config(config)?;
+
+        Ok(settings)
+    }
+
+    /// Replaces the settings that are present in the configuration, leaving the others unchanged. Nothing is
+    /// changed if any of them are invalid.
+    pub fn merge_config(&mut self, config: &Config) -> Result<(), String> {
+        let mut merged = self.clone();
+        merged.read_config(config)?;
+
+        merged.zoom = merged.zoom.to_uppercase();
+        merged.rotate = merged.rotate.rem_euclid(360.0);
+
+        *self = merged;
+        Ok(())
+    }
+
+    /// The configuration that the renderer is created from.
+    pub fn to_config(&self) -> Config {
+        let mut config = Config::default();
+        self.write_to(&mut config);
+
+        config
+    }
+
+    /// The data that the renderer calculates for the colouring method.
+    pub fn data_type(&self) -> DataType {
+        match self.coloring_type.to_ascii_uppercase().as_ref() {
+            "SMOOTH_ITERATION" | "SMOOTH" | "STEP_ITERATION" | "STEP" => DataType::Iteration,
+            "STRIPE" => DataType::Stripe,
+            "DISTANCE_STRIPE" => DataType::DistanceStripe,
+            _ => DataType::Distance
+        }
+    }
+}
+
+/// Loads the default settings. A missing file uses the defaults in the schema, but an invalid one is an error.
+/// The palette is returned if the file defines one, and the warnings are for the caller to show.
+pub fn load_settings(path: &Path) -> Result<(Settings, Option<Palette>, Vec<String>), String> {
+    let mut config = Config::default();
+    let mut warnings = Vec::new();
+
+    if path.exists() {
+        config.merge(File::from(path)).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    } else {
+        warnings.push(format!("{} not found, using the default settings", path.display()));
+    }
+
+    warnings.extend(version_warning(&config));
+    migrate_settings(&mut config);
+
+    let settings = Settings::from_config(&config).map_err(|e| format!("Error in {}: {}", path.display(), e))?;
+    let palette = Palette::from_config(&config, &mut warnings);
+
+    Ok((settings, palette, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use config::FileFormat;
+
+    use super::*;
+
+    // The value read back by the TOML parser used for configuration files
+    fn read_back(value: &str) -> String {
+        let mut config = Config::default();
+        config.merge(File::from_str(&format!("value = {}", toml_string(value)), FileFormat::Toml)).unwrap();
+
+        config.get_str("value").unwrap()
+    }
+
+    #[test]
+    fn toml_string_escapes() {
+        assert_eq!(toml_string("plain"), "\"plain\"");
+        assert_eq!(toml_string("a \"quoted\" C:\\path"), "\"a \\\"quoted\\\" C:\\\\path\"");
+        assert_eq!(toml_string("line\nbreak\ttab"), "\"line\\nbreak\\ttab\"");
+        assert_eq!(toml_string("\u{1b}[0m"), "\"\\u001B[0m\"");
+    }
+
+    #[test]
+    fn toml_string_round_trip() {
+        for value in ["", "mandelbrot", "a \"quoted\" C:\\path", "line\r\nbreak\ttab", "\u{1b}[0m\u{7f}", "ünïcödé ∞"] {
+            assert_eq!(read_back(value), value);
+        }
+    }
+}
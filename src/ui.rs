@@ -1,13 +1,13 @@
 use std::{fmt::Display, str::FromStr};
 use druid::{commands::CLOSE_WINDOW, 
     widget::{Align, Button,
-        Checkbox, CrossAxisAlignment, FillStrat, Flex, Image, Label, ProgressBar, Slider, Split, TextBox, WidgetExt, Painter}, 
-    Command, Target, RenderContext};
-use druid::{Widget, ImageBuf, Data, LensExt, Menu, LocalizedString, MenuItem, SysMods, Env, WindowId, WindowDesc};
+        Checkbox, CrossAxisAlignment, FillStrat, Flex, Image, Label, List, ProgressBar, Slider, Split, TextBox, WidgetExt, Painter, SizedBox, LineBreaking}, 
+    Command, Target, RenderContext, Rect, Size};
+use druid::{Widget, ImageBuf, Data, Lens, LensExt, Menu, LocalizedString, MenuItem, SysMods, Env, WindowId, WindowDesc, FileDialogOptions, FileSpec, Color, TimerToken};
 use druid::piet::{ImageFormat, InterpolationMode};
 use druid::text::ParseFormatter;
-use druid::commands::CLOSE_ALL_WINDOWS;
-use druid::theme::{PRIMARY_DARK, BACKGROUND_DARK};
+use druid::commands::{CLOSE_ALL_WINDOWS, SHOW_OPEN_PANEL, SHOW_SAVE_PANEL};
+use druid::theme::{PRIMARY_DARK, BACKGROUND_DARK, BUTTON_DARK};
 
 use druid_widget_nursery::DropdownSelect;
 
@@ -19,12 +19,50 @@ use crate::widgets::*;
 use crate::custom::*;
 use crate::commands::*;
 use crate::lens;
+use crate::history::NavigationHistory;
+use crate::bookmarks::Bookmark;
+use crate::pixel::LOUPE_SIZE;
+use crate::sequence::SequenceFormat;
+use crate::export::SUPERSAMPLING_FACTORS;
+use crate::batch::BatchJob;
+use crate::palette::{Palette, PaletteStop, PaletteInterpolation};
+use crate::progress::{RenderProgress, RenderStage, format_time, format_time_short};
+use crate::notifications::{Notification, NotificationLevel};
+use crate::settings::Settings;
 
+/// The formulas that can be chosen in the FRACTAL group. Each is stored as the `fractal_type` and
+/// `fractal_power` settings.
 #[derive(Data, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
 pub enum FractalType {
-    A,
-    B,
-    C
+    Mandelbrot2,
+    BurningShip2,
+    Mandelbrot3
+}
+
+impl FractalType {
+    pub fn from_settings(settings: &Settings) -> Self {
+        match (settings.fractal_type.to_ascii_uppercase().as_ref(), settings.fractal_power) {
+            ("BURNING_SHIP", _) => FractalType::BurningShip2,
+            ("MANDELBROT", 3) => FractalType::Mandelbrot3,
+            _ => FractalType::Mandelbrot2
+        }
+    }
+
+    pub fn apply(&self, settings: &mut Settings) {
+        let (fractal_type, fractal_power) = match self {
+            FractalType::Mandelbrot2 => ("mandelbrot", 2),
+            FractalType::BurningShip2 => ("burning_ship", 2),
+            FractalType::Mandelbrot3 => ("mandelbrot", 3),
+        };
+
+        settings.fractal_type = fractal_type.to_string();
+        settings.fractal_power = fractal_power;
+    }
+
+    // The period detection and Newton steps are only written for the quadratic Mandelbrot set
+    pub fn supports_root_finding(&self) -> bool {
+        *self == FractalType::Mandelbrot2
+    }
 }
 
 pub fn window_main(renderer: Arc<Mutex<FractalRenderer>>) -> impl Widget<FractalData> {
@@ -38,9 +76,21 @@ pub fn window_main(renderer: Arc<Mutex<FractalRenderer>>) -> impl Widget<Fractal
         root_pos_current: (0.0, 0.0),
         cached_image: None,
         needs_buffer_refresh: true,
+        preview: None,
+        cached_preview: None,
         mouse_mode: MouseMode::None,
         renderer_zoom: FloatExtended::new(0.0, 0),
         renderer_rotate: (0.0, 0.0),
+        history: NavigationHistory::new(),
+        root_snapshot: None,
+        sequence: None,
+        toast_timer: TimerToken::INVALID,
+        batch_next_id: 0,
+        wheel_timer: TimerToken::INVALID,
+        wheel_zoom: None,
+        wheel_sent: None,
+        wheel_pending: false,
+        window_size: Size::ZERO,
     });
 
     let group_image_size = Flex::column()
@@ -104,6 +154,23 @@ pub fn window_main(renderer: Arc<Mutex<FractalRenderer>>) -> impl Widget<Fractal
                 ctx.submit_command(MULTIPLY_ZOOM.with(1.0 / data.zoom_scale_factor));
             }).expand_width().fix_height(24.0), 1.0))
         .with_spacer(4.0)
+        .with_child(Flex::row()
+            .with_flex_child(Button::new(|data: &usize, _: &Env| {
+                    if *data == 1 {
+                        "CANCEL".to_string()
+                    } else {
+                        "ZOOM BOX".to_string()
+                    }
+                }).on_click(|_ctx, data: &mut usize, _env| {
+                    *data = if *data == 1 {
+                        0
+                    } else {
+                        1
+                    };
+                }).lens(FractalData::mouse_mode).expand_width().fix_height(24.0), 1.0)
+            .with_spacer(4.0)
+            .with_flex_child(create_checkbox_row("Lock aspect").lens(FractalData::zoom_box_locked), 1.0))
+        .with_spacer(4.0)
         .with_child(Flex::row()
             .with_child(Label::new("Iterations:").with_text_size(14.0))
             .with_flex_spacer(1.0)
@@ -211,19 +278,21 @@ pub fn window_main(renderer: Arc<Mutex<FractalRenderer>>) -> impl Widget<Fractal
             }).expand_width(), 0.25))
         .with_spacer(4.0)
         .with_child(Flex::row()
-            .with_flex_child(Button::new(|data: &usize, _: &Env| {
-                    if *data == 0 {
-                        "DRAW BOX".to_string()
-                    } else {
+            .with_flex_child(Button::new(|data: &FractalData, _: &Env| {
+                    if !data.fractal_type.supports_root_finding() {
+                        "UNSUPPORTED".to_string()
+                    } else if data.mouse_mode == 2 {
                         "CANCEL".to_string()
+                    } else {
+                        "DRAW BOX".to_string()
                     }
-                }).on_click(|_ctx, data: &mut usize, _env| {
-                    *data = if *data == 0 {
+                }).on_click(|_ctx, data: &mut FractalData, _env| {
+                    data.mouse_mode = if data.mouse_mode != 2 && data.fractal_type.supports_root_finding() {
                         2
                     } else {
                         0
                     };
-                }).lens(FractalData::mouse_mode).expand_width().fix_height(24.0), 1.0)
+                }).expand_width().fix_height(24.0), 1.0)
             .with_spacer(4.0)
             .with_flex_child(Button::new("CENTRAL OUT").on_click(|ctx, data: &mut FractalData, _env| {
                 ctx.submit_command(MULTIPLY_PATTERN.with(1.0 - 1.0 / (1.0 - data.root_zoom_factor)));
@@ -235,11 +304,13 @@ pub fn window_main(renderer: Arc<Mutex<FractalRenderer>>) -> impl Widget<Fractal
 
     let group_palette = Flex::column()
             .with_child(Label::new("FRACTAL").with_text_size(20.0).expand_width())
-            // .with_child(DropdownSelect::new(vec![
-            //     ("Mandelbrot 2", FractalType::A),
-            //     ("Burning Ship 2", FractalType::B),
-            //     ("Mandelbrot 3", FractalType::C),
-            // ]).align_left().lens(FractalData::fractal_type))
+            .with_spacer(4.0)
+            .with_child(DropdownSelect::new(vec![
+                ("Mandelbrot 2", FractalType::Mandelbrot2),
+                ("Burning Ship 2", FractalType::BurningShip2),
+                ("Mandelbrot 3", FractalType::Mandelbrot3),
+            ]).align_left().lens(FractalData::fractal_type).controller(FractalTypeController))
+            .with_spacer(4.0)
         .with_child(Flex::row()
             .with_flex_child(Label::new("COLORING").with_text_size(20.0).expand_width(), 0.5)
             .with_flex_child(Label::new(|data: &FractalData, _env: &_| {
@@ -262,6 +333,10 @@ pub fn window_main(renderer: Arc<Mutex<FractalRenderer>>) -> impl Widget<Fractal
             .fix_height(24.0)
             .expand_width())
         .with_spacer(4.0)
+        .with_child(Button::new("EDIT PALETTE").on_click(|ctx, _data: &mut FractalData, _env| {
+            ctx.submit_command(OPEN_PALETTE_EDITOR);
+        }).expand_width().fix_height(24.0))
+        .with_spacer(4.0)
         .with_child(Slider::new()
             .with_range(0.0, 1.0)
             .expand_width()
@@ -385,34 +460,63 @@ pub fn window_main(renderer: Arc<Mutex<FractalRenderer>>) -> impl Widget<Fractal
         .with_child(Flex::row()
             .with_flex_child(Label::<FractalData>::new("Render:").with_text_size(14.0).expand_width(), 1.0)
             .with_child(NoUpdateLabel::new(12.0).lens(FractalData::rendering_stage.map(|val| {
-                let text = match val {
-                    1 => "Reference",
-                    2 | 3 => "Approximation",
-                    4 => "Iteration",
-                    5 => "Correction",
-                    _ => "Complete",
-                };
-    
-                format!("{:>14}", text)
+                format!("{:>14}", val.name())
             }, |_, _| {})))
             .with_child(NoUpdateLabel::new(12.0).lens(FractalData::reference_count.map(|val| {
                 format!("{:>8}", format!("Ref:{}", val))
             }, |_, _| {})))
-            .with_child(NoUpdateLabel::new(12.0).lens(FractalData::rendering_time.map(|val| {
-                let ms = val % 1000;
-                let s = val / 1000;
-                let m = s / 60;
-                let h = m / 60;
-    
-                format!("{}:{:0>2}:{:0>2}.{:0>3}", h, m % 60, s % 60, ms)
+            .with_child(NoUpdateLabel::new(12.0).lens(FractalData::render_progress.map(|val| {
+                format_time(val.time)
+            }, |_, _| {}))))
+        .with_spacer(4.0)
+        .with_child(Flex::row()
+            .with_flex_child(Label::<FractalData>::new("Remaining:").with_text_size(14.0).expand_width(), 1.0)
+            .with_child(NoUpdateLabel::new(12.0).lens(FractalData::render_progress.map(|val| {
+                match val.remaining_total {
+                    Some(remaining) if val.stage != RenderStage::Complete => format!("{:>18}", format_time(remaining)),
+                    _ => format!("{:>18}", "-"),
+                }
             }, |_, _| {}))))
         .with_spacer(4.0)
+        .with_child(Painter::new(|ctx, data: &RenderProgress, env| {
+                let bounds = ctx.size().to_rect();
+                let width = bounds.width() / RenderStage::ACTIVE.len() as f64;
+
+                // One segment per stage, filled by how much of that stage is complete
+                for (index, stage) in RenderStage::ACTIVE.iter().enumerate() {
+                    let x0 = index as f64 * width + 1.0;
+                    let x1 = (index + 1) as f64 * width - 1.0;
+
+                    ctx.fill(Rect::new(x0, 0.0, x1, bounds.height()), &env.get(BUTTON_DARK));
+
+                    let fraction = data.stage_fraction(*stage);
+
+                    if fraction > 0.0 {
+                        ctx.fill(Rect::new(x0, 0.0, x0 + (x1 - x0) * fraction, bounds.height()), &env.get(PRIMARY_DARK));
+                    }
+                }
+            }).fix_height(6.0).expand_width().lens(FractalData::render_progress))
+        .with_spacer(2.0)
+        .with_child({
+            let mut stage_times = Flex::row();
+
+            for stage in RenderStage::ACTIVE.iter() {
+                let stage = *stage;
+
+                stage_times = stage_times.with_flex_child(NoUpdateLabel::new(10.0).lens(FractalData::render_progress.map(move |val| {
+                    format!("{} {}", stage.short_name(), format_time_short(val.stage_times[stage.index().unwrap()]))
+                }, |_, _| {})), 1.0);
+            }
+
+            stage_times
+        })
+        .with_spacer(4.0)
         .with_child(Flex::row()
-            .with_flex_child(ProgressBar::new().lens(FractalData::rendering_progress).expand_width(), 0.75)
+            .with_flex_child(ProgressBar::new().lens(FractalData::render_progress.map(|val| val.progress, |_, _| {})).expand_width(), 0.75)
             .with_spacer(4.0)
             .with_flex_child(Button::new(|data: &FractalData, _env: &_| {
                 match data.rendering_stage {
-                    0 => {
+                    RenderStage::Complete => {
                         if data.zoom_out_enabled {
                             "CANCEL".to_string()
                         } else {
@@ -424,7 +528,7 @@ pub fn window_main(renderer: Arc<Mutex<FractalRenderer>>) -> impl Widget<Fractal
                     }
                 }
             }).on_click(|ctx, data: &mut FractalData, _env| {
-                if data.rendering_stage == 0 && !data.zoom_out_enabled {
+                if data.rendering_stage == RenderStage::Complete && !data.zoom_out_enabled {
                     // TODO maybe add a section here that checks if a zoom out sequence is ongoing
                     ctx.submit_command(RESET_RENDERER_FAST);
                 } else {
@@ -433,28 +537,39 @@ pub fn window_main(renderer: Arc<Mutex<FractalRenderer>>) -> impl Widget<Fractal
                 }
             }).expand_width(), 0.25));
 
-    let _group_pixel_information = Flex::column()
+    let group_pixel_information = Flex::column()
         .with_child(Flex::row()
-            .with_flex_spacer(0.5)
             .with_child(
-                Image::new(ImageBuf::from_raw(vec![0u8; 81 * 3], ImageFormat::Rgb, 9, 9))
+                Image::new(ImageBuf::from_raw(vec![0u8; LOUPE_SIZE * LOUPE_SIZE * 3], ImageFormat::Rgb, LOUPE_SIZE, LOUPE_SIZE))
                     .interpolation_mode(InterpolationMode::NearestNeighbor)
                     .fill_mode(FillStrat::Contain)
                     .controller(PixelInformationUpdateController)
-                    .fix_height(100.0))
+                    .fix_size(90.0, 90.0))
             .with_spacer(4.0)
-            .with_child(Flex::column()
+            .with_flex_child(Flex::column()
                 .with_child(NoUpdateLabel::new(12.0).lens(FractalData::pixel_pos.map(|val| {
                     format!("{:>13}", format!("({},{})", val[0], val[1]))
                 }, |_, _| {})))
-                .with_child(Flex::row()
-                    .with_child(NoUpdateLabel::new(12.0).lens(FractalData::pixel_iterations.map(|val| {
-                        format!("{:>8}", val)
-                    }, |_, _| {})))
-                    .with_child(NoUpdateLabel::new(12.0).lens(FractalData::pixel_smooth.map(|val| {
-                        format!("{:>.4}", val)
-                    }, |_, _| {})))))
-            .with_flex_spacer(0.5));
+                .with_child(NoUpdateLabel::new(12.0).lens(FractalData::pixel_iterations.map(|val| {
+                    format!("{:<10}{:>10}", "Iter:", val)
+                }, |_, _| {})))
+                .with_child(NoUpdateLabel::new(12.0).lens(FractalData::pixel_smooth.map(|val| {
+                    format!("{:<10}{:>10.4}", "Smooth:", val)
+                }, |_, _| {})))
+                .with_child(NoUpdateLabel::new(12.0).lens(FractalData::pixel_distance.map(|val| {
+                    format!("{:<10}{:>10.4}", "Distance:", val)
+                }, |_, _| {})))
+                .with_child(NoUpdateLabel::new(12.0).lens(FractalData::pixel_stripe.map(|val| {
+                    format!("{:<10}{:>10.4}", "Stripe:", val)
+                }, |_, _| {})))
+                .with_child(NoUpdateLabel::new(12.0).lens(FractalData::pixel_glitched.map(|val| {
+                    format!("{:<10}{:>10}", "Glitched:", if *val { "yes" } else { "no" })
+                }, |_, _| {})))
+                .cross_axis_alignment(CrossAxisAlignment::Start), 1.0))
+        .with_spacer(4.0)
+        .with_child(TextBox::multiline().with_text_size(10.0).expand_width().lens(FractalData::pixel_real))
+        .with_spacer(2.0)
+        .with_child(TextBox::multiline().with_text_size(10.0).expand_width().lens(FractalData::pixel_imag));
 
     let group_general_information = Flex::column()
         .with_child(Label::new(format!("rust-fractal-gui {}", env!("CARGO_PKG_VERSION"))))
@@ -468,6 +583,8 @@ pub fn window_main(renderer: Arc<Mutex<FractalRenderer>>) -> impl Widget<Fractal
         .with_spacer(4.0)
         .with_child(create_checkbox_row("Show glitched pixels").lens(FractalData::display_glitches))
         .with_spacer(4.0)
+        .with_child(create_checkbox_row("Preview while navigating").lens(FractalData::preview_enabled))
+        .with_spacer(4.0)
         .with_child(Flex::row()
             .with_child(Label::new("Glitch tolerance:"))
             .with_flex_child(Slider::new()
@@ -542,6 +659,40 @@ pub fn window_main(renderer: Arc<Mutex<FractalRenderer>>) -> impl Widget<Fractal
             ctx.submit_command(SET_ADVANCED_OPTIONS);
         }).expand_width().fix_height(40.0));
 
+    let group_bookmarks = Flex::column()
+        .with_child(Label::new("BOOKMARKS").with_text_size(20.0).expand_width())
+        .with_spacer(4.0)
+        .with_child(Flex::row()
+            .with_flex_child(TextBox::new()
+                .with_placeholder("Name")
+                .expand_width()
+                .lens(FractalData::bookmark_name), 1.0)
+            .with_spacer(4.0)
+            .with_child(Button::new("ADD").on_click(|ctx, _data: &mut FractalData, _env| {
+                ctx.submit_command(ADD_BOOKMARK);
+            }).fix_width(80.0).fix_height(24.0)))
+        .with_spacer(8.0)
+        .with_child(List::new(create_bookmark_row)
+            .with_spacing(4.0)
+            .scroll()
+            .vertical()
+            .fix_height(400.0)
+            .lens(FractalData::bookmarks));
+
+    let group_log = Flex::column()
+        .with_child(Flex::row()
+            .with_flex_child(Label::new("LOG").with_text_size(20.0).expand_width(), 1.0)
+            .with_child(Button::new("CLEAR").on_click(|ctx, _data: &mut FractalData, _env| {
+                ctx.submit_command(CLEAR_NOTIFICATIONS);
+            }).fix_width(80.0).fix_height(24.0)))
+        .with_spacer(8.0)
+        .with_child(List::new(create_notification_row)
+            .with_spacing(4.0)
+            .scroll()
+            .vertical()
+            .fix_height(400.0)
+            .lens(FractalData::notifications));
+
     let tabs_menu = Either::new(|data: &FractalData, _env| data.current_tab)
         .add_branch(Flex::column()
             .with_child(group_image_size)
@@ -549,7 +700,34 @@ pub fn window_main(renderer: Arc<Mutex<FractalRenderer>>) -> impl Widget<Fractal
             .with_child(group_palette)
         )
         .add_branch(group_location)
-        .add_branch(group_advanced_options);
+        .add_branch(group_advanced_options)
+        .add_branch(group_bookmarks)
+        .add_branch(group_log);
+
+    // The most recent notification, shown until it times out or is dismissed
+    let toast = Either::new(|data: &FractalData, _env| if data.toast.is_some() { 1 } else { 0 })
+        .add_branch(SizedBox::empty())
+        .add_branch(Flex::row()
+            .with_flex_child(Label::new(|data: &FractalData, _env: &_| {
+                match &data.toast {
+                    Some(notification) => format!("{}: {}", notification.level.name(), notification.message),
+                    None => String::new()
+                }
+            }).with_line_break_mode(LineBreaking::WordWrap).expand_width(), 1.0)
+            .with_spacer(4.0)
+            .with_child(Button::new("X").on_click(|ctx, _data: &mut FractalData, _env| {
+                ctx.submit_command(DISMISS_NOTIFICATION);
+            }).fix_width(32.0).fix_height(24.0))
+            .padding(8.0)
+            .background(Painter::new(|ctx, data: &FractalData, env| {
+                let bounds = ctx.size().to_rect();
+
+                match data.toast.as_ref().map(|notification| notification.level) {
+                    Some(NotificationLevel::Error) => ctx.fill(bounds, &Color::rgb8(140, 30, 30)),
+                    Some(NotificationLevel::Warning) => ctx.fill(bounds, &Color::rgb8(140, 100, 20)),
+                    _ => ctx.fill(bounds, &env.get(PRIMARY_DARK)),
+                }
+            })));
 
     let tabs_selector = Flex::row()
         .with_flex_child(Button::from_label(Label::new("IMAGE").with_text_size(16.0)).on_click(|_ctx, data: &mut FractalData, _env| {
@@ -560,6 +738,12 @@ pub fn window_main(renderer: Arc<Mutex<FractalRenderer>>) -> impl Widget<Fractal
         }).expand_width().fix_height(40.0), 1.0)
         .with_flex_child(Button::from_label(Label::new("ADVANCED").with_text_size(16.0)).on_click(|_ctx, data: &mut FractalData, _env| {
             data.current_tab = 2;
+        }).expand_width().fix_height(40.0), 1.0)
+        .with_flex_child(Button::from_label(Label::new("BOOKMARKS").with_text_size(16.0)).on_click(|_ctx, data: &mut FractalData, _env| {
+            data.current_tab = 3;
+        }).expand_width().fix_height(40.0), 1.0)
+        .with_flex_child(Button::from_label(Label::new("LOG").with_text_size(16.0)).on_click(|_ctx, data: &mut FractalData, _env| {
+            data.current_tab = 4;
         }).expand_width().fix_height(40.0), 1.0);
 
     let tabs_indicator = Flex::row()
@@ -586,12 +770,29 @@ pub fn window_main(renderer: Arc<Mutex<FractalRenderer>>) -> impl Widget<Fractal
                 } else {
                     ctx.fill(bounds, &env.get(BACKGROUND_DARK));
                 }
+            }).fix_height(2.0).lens(FractalData::current_tab), 1.0)
+        .with_flex_child(Painter::new(|ctx, data: &usize, env| {
+                let bounds = ctx.size().to_rect();
+                if *data == 3 {
+                    ctx.fill(bounds, &env.get(PRIMARY_DARK));
+                } else {
+                    ctx.fill(bounds, &env.get(BACKGROUND_DARK));
+                }
+            }).fix_height(2.0).lens(FractalData::current_tab), 1.0)
+        .with_flex_child(Painter::new(|ctx, data: &usize, env| {
+                let bounds = ctx.size().to_rect();
+                if *data == 4 {
+                    ctx.fill(bounds, &env.get(PRIMARY_DARK));
+                } else {
+                    ctx.fill(bounds, &env.get(BACKGROUND_DARK));
+                }
             }).fix_height(2.0).lens(FractalData::current_tab), 1.0);
 
     // TODO have a help and about menu
     let side_menu = Flex::column()
         .with_child(tabs_selector)
         .with_child(tabs_indicator)
+        .with_child(toast)
         .with_spacer(8.0)
         .with_flex_child(Flex::row()
             .with_flex_spacer(0.05)
@@ -599,7 +800,7 @@ pub fn window_main(renderer: Arc<Mutex<FractalRenderer>>) -> impl Widget<Fractal
                 .with_flex_child(Flex::column()
                     .with_child(tabs_menu)
                     .with_flex_spacer(1.0), 1.0)
-                // .with_child(group_pixel_information)
+                .with_child(group_pixel_information)
                 .with_spacer(24.0)
                 .with_child(group_information)
                 .with_spacer(24.0)
@@ -624,6 +825,56 @@ fn create_label_textbox_row<T: Data + Display + FromStr>(label: &str, width: f64
         .with_flex_child(text_box, 1.0)
 }
 
+fn create_bookmark_row() -> impl Widget<Bookmark> {
+    let thumbnail = Painter::new(|ctx, data: &Bookmark, env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &env.get(BACKGROUND_DARK));
+
+        if data.thumbnail.is_empty() {
+            return;
+        }
+
+        if let Ok(image) = ctx.make_image(data.thumbnail_width, data.thumbnail_height, &data.thumbnail, ImageFormat::Rgb) {
+            // Keep the aspect ratio of the saved image
+            let scale = (bounds.width() / data.thumbnail_width as f64).min(bounds.height() / data.thumbnail_height as f64);
+            let size = Size::new(scale * data.thumbnail_width as f64, scale * data.thumbnail_height as f64);
+
+            ctx.draw_image(&image, Rect::from_center_size(bounds.center(), size), InterpolationMode::Bilinear);
+        }
+    }).fix_size(96.0, 64.0);
+
+    let description = Flex::column()
+        .with_child(Label::new(|data: &Bookmark, _env: &_| {
+            data.name.clone()
+        }).with_text_size(14.0))
+        .with_spacer(2.0)
+        .with_child(Label::new(|data: &Bookmark, _env: &_| {
+            format!("Zoom: {}", extended_to_string_short(string_to_extended(&data.zoom)))
+        }).with_text_size(12.0))
+        .with_child(Label::new(|data: &Bookmark, _env: &_| {
+            if data.period == 0 {
+                "Period: -".to_string()
+            } else {
+                format!("Period: {}", data.period)
+            }
+        }).with_text_size(12.0))
+        .cross_axis_alignment(CrossAxisAlignment::Start);
+
+    // Only the thumbnail and description navigate so that deleting does not also go to the bookmark
+    Flex::row()
+        .with_flex_child(Flex::row()
+            .with_child(thumbnail)
+            .with_spacer(8.0)
+            .with_flex_child(description.expand_width(), 1.0)
+            .on_click(|ctx, data: &mut Bookmark, _env| {
+                ctx.submit_command(GO_TO_BOOKMARK.with(data.clone()));
+            }), 1.0)
+        .with_spacer(4.0)
+        .with_child(Button::new("X").on_click(|ctx, data: &mut Bookmark, _env| {
+            ctx.submit_command(DELETE_BOOKMARK.with(data.clone()));
+        }).fix_width(32.0).fix_height(24.0))
+}
+
 fn create_checkbox_row(label: &str) -> impl Widget<bool> {
     let label = Label::<bool>::new(label)
         .expand_width();
@@ -643,10 +894,18 @@ pub fn make_menu(_: Option<WindowId>, _state: &FractalData, _: &Env) -> Menu<Fra
             .entry(MenuItem::new(LocalizedString::new("Save Location")).command(SAVE_LOCATION))
             .entry(MenuItem::new(LocalizedString::new("Save Image")).command(SAVE_IMAGE).hotkey(SysMods::Cmd, "s"))
             .entry(MenuItem::new(LocalizedString::new("Save Configuration")).command(SAVE_ALL))
+            .entry(MenuItem::new(LocalizedString::new("Export...")).command(OPEN_EXPORT_WINDOW))
+            .entry(MenuItem::new(LocalizedString::new("Batch Render")).command(OPEN_BATCH_WINDOW))
+            .entry(MenuItem::new(LocalizedString::new("Export Iteration Map")).command(EXPORT_ITERATION_MAP))
+            .entry(MenuItem::new(LocalizedString::new("Import Iteration Map")).command(IMPORT_ITERATION_MAP))
             .entry(MenuItem::new(LocalizedString::new("Zoom Out Default")).command(ZOOM_OUT))
             .entry(MenuItem::new(LocalizedString::new("Zoom Out Removed")).command(ZOOM_OUT_OPTIMISED))
+            .entry(MenuItem::new(LocalizedString::new("Render Zoom Sequence")).command(OPEN_SEQUENCE_WINDOW))
+            .entry(MenuItem::new(LocalizedString::new("Resume Sequence")).command(OPEN_SEQUENCE_MANIFEST))
             .entry(MenuItem::new(LocalizedString::new("Exit")).command(CLOSE_ALL_WINDOWS)))
         .entry(Menu::new(LocalizedString::new("common-menu-edit-menu"))
+            .entry(MenuItem::new(LocalizedString::new("Back")).command(HISTORY_BACK))
+            .entry(MenuItem::new(LocalizedString::new("Forward")).command(HISTORY_FORWARD))
             .entry(MenuItem::new(LocalizedString::new("Reset")).command(RESET_DEFAULT_LOCATION).hotkey(SysMods::Cmd, "r"))
             .entry(druid::platform_menus::common::cut())
             .entry(druid::platform_menus::common::copy())
@@ -657,6 +916,8 @@ pub fn make_menu(_: Option<WindowId>, _state: &FractalData, _: &Env) -> Menu<Fra
             .entry(MenuItem::new(LocalizedString::new("Distance")).command(SET_COLORING_METHOD.with(ColoringType::Distance)))
             .entry(MenuItem::new(LocalizedString::new("Stripe")).command(SET_COLORING_METHOD.with(ColoringType::Stripe)))
             .entry(MenuItem::new(LocalizedString::new("Distance Stripe")).command(SET_COLORING_METHOD.with(ColoringType::DistanceStripe)))
+            .entry(MenuItem::new(LocalizedString::new("Edit Palette")).command(OPEN_PALETTE_EDITOR))
+            .entry(MenuItem::new(LocalizedString::new("Load Palette")).command(LOAD_PALETTE))
     )
 }
 
@@ -703,4 +964,324 @@ pub fn window_location() -> impl Widget<FractalData> {
         .with_flex_spacer(0.05)
         .scroll()
         .vertical()
+}
+
+pub fn window_sequence() -> impl Widget<FractalData> {
+    Flex::row()
+        .with_flex_spacer(0.05)
+        .with_flex_child(Flex::column()
+            .with_spacer(8.0)
+            .with_child(Label::new("Directory:").with_text_size(14.0))
+            .with_spacer(8.0)
+            .with_child(Flex::row()
+                .with_flex_child(TextBox::new().with_text_size(10.0).expand_width().lens(FractalData::sequence_directory), 1.0)
+                .with_spacer(4.0)
+                .with_child(Button::new("BROWSE").on_click(|ctx, _data: &mut FractalData, _env| {
+                    let open_dialog_options = FileDialogOptions::new()
+                        .select_directories()
+                        .accept_command(SELECT_SEQUENCE_DIRECTORY);
+
+                    ctx.submit_command(SHOW_OPEN_PANEL.with(open_dialog_options));
+                }).fix_height(24.0)))
+            .with_spacer(8.0)
+            .with_child(Label::new("File name ({frame} is replaced with the frame number):").with_text_size(14.0))
+            .with_spacer(8.0)
+            .with_child(TextBox::new().with_text_size(10.0).expand_width().lens(FractalData::sequence_template))
+            .with_spacer(8.0)
+            .with_child(Flex::row()
+                .with_child(Label::new("Format:").with_text_size(14.0).fix_width(160.0))
+                .with_child(DropdownSelect::new(vec![
+                    ("PNG", SequenceFormat::Png),
+                    ("JPG", SequenceFormat::Jpg),
+                ]).lens(FractalData::sequence_format)))
+            .with_spacer(8.0)
+            .with_child(create_label_textbox_row("Frames (0 for all):", 160.0)
+                .lens(FractalData::sequence_frames))
+            .with_spacer(8.0)
+            .with_child(create_label_textbox_row("Frame offset:", 160.0)
+                .lens(FractalData::sequence_frame_offset))
+            .with_spacer(8.0)
+            .with_child(create_checkbox_row("Remove image centre").lens(FractalData::sequence_remove_centre))
+            .with_spacer(8.0)
+            .with_child(Flex::row()
+                .with_flex_spacer(0.25)
+                .with_flex_child(Button::new("START").on_click(|ctx, _data: &mut FractalData, _env| {
+                    ctx.submit_command(Command::new(START_SEQUENCE, (), Target::Global));
+                    ctx.submit_command(CLOSE_WINDOW);
+                }).expand_width().fix_height(32.0), 0.25)
+                .with_spacer(4.0)
+                .with_flex_child(Button::new("CANCEL").on_click(|ctx, _data: &mut FractalData, _env| {
+                    ctx.submit_command(CLOSE_WINDOW);
+                }).expand_width().fix_height(32.0), 0.25)
+                .with_flex_spacer(0.25))
+            .with_spacer(8.0)
+            .cross_axis_alignment(CrossAxisAlignment::Start), 0.9)
+        .with_flex_spacer(0.05)
+        .controller(SequenceDirectoryController)
+}
+
+pub fn window_export() -> impl Widget<FractalData> {
+    let factors = SUPERSAMPLING_FACTORS.iter()
+        .map(|factor| (if *factor == 1 {
+            "None".to_string()
+        } else {
+            format!("{}x", factor)
+        }, *factor))
+        .collect::<Vec<(String, usize)>>();
+
+    Flex::row()
+        .with_flex_spacer(0.05)
+        .with_flex_child(Flex::column()
+            .with_spacer(8.0)
+            .with_child(create_label_textbox_row("Width:", 160.0)
+                .lens(FractalData::export_width))
+            .with_spacer(8.0)
+            .with_child(create_label_textbox_row("Height:", 160.0)
+                .lens(FractalData::export_height))
+            .with_spacer(8.0)
+            .with_child(Flex::row()
+                .with_child(Label::new("Supersampling:").with_text_size(14.0).fix_width(160.0))
+                .with_child(DropdownSelect::new(factors).lens(FractalData::export_supersampling)))
+            .with_spacer(8.0)
+            .with_child(create_checkbox_row("Render in tiles (PNG only)").lens(FractalData::export_tiled))
+            .with_spacer(8.0)
+            .with_child(Label::new(|data: &FractalData, _env: &_| {
+                match data.export_progress {
+                    Some((completed, total)) => format!("Exporting: {} of {} tiles complete", completed, total),
+                    None => format!("Rendered at {}x{}", data.export_width * data.export_supersampling, data.export_height * data.export_supersampling)
+                }
+            }).with_text_size(12.0))
+            .with_spacer(8.0)
+            .with_child(Flex::row()
+                .with_flex_spacer(0.25)
+                .with_flex_child(Button::new("EXPORT").on_click(|ctx, _data: &mut FractalData, _env| {
+                    ctx.submit_command(Command::new(START_EXPORT, (), Target::Global));
+                }).expand_width().fix_height(32.0), 0.25)
+                .with_spacer(4.0)
+                .with_flex_child(Button::new("STOP").on_click(|ctx, _data: &mut FractalData, _env| {
+                    ctx.submit_command(Command::new(STOP_EXPORT, (), Target::Global));
+                }).expand_width().fix_height(32.0), 0.25)
+                .with_spacer(4.0)
+                .with_flex_child(Button::new("CLOSE").on_click(|ctx, _data: &mut FractalData, _env| {
+                    ctx.submit_command(CLOSE_WINDOW);
+                }).expand_width().fix_height(32.0), 0.25)
+                .with_flex_spacer(0.25))
+            .with_spacer(8.0)
+            .cross_axis_alignment(CrossAxisAlignment::Start), 0.9)
+        .with_flex_spacer(0.05)
+}
+
+pub fn window_batch() -> impl Widget<FractalData> {
+    Flex::row()
+        .with_flex_spacer(0.05)
+        .with_flex_child(Flex::column()
+            .with_spacer(8.0)
+            .with_child(Label::new("Output directory:").with_text_size(14.0))
+            .with_spacer(8.0)
+            .with_child(Flex::row()
+                .with_flex_child(TextBox::new().with_text_size(10.0).expand_width().lens(FractalData::batch_directory), 1.0)
+                .with_spacer(4.0)
+                .with_child(Button::new("BROWSE").on_click(|ctx, _data: &mut FractalData, _env| {
+                    let open_dialog_options = FileDialogOptions::new()
+                        .select_directories()
+                        .accept_command(SELECT_BATCH_DIRECTORY);
+
+                    ctx.submit_command(SHOW_OPEN_PANEL.with(open_dialog_options));
+                }).fix_height(24.0)))
+            .with_spacer(8.0)
+            .with_child(Flex::row()
+                .with_child(Label::new("Format:").with_text_size(14.0).fix_width(160.0))
+                .with_child(DropdownSelect::new(vec![
+                    ("PNG", SequenceFormat::Png),
+                    ("JPG", SequenceFormat::Jpg),
+                ]).lens(FractalData::batch_format)))
+            .with_spacer(8.0)
+            .with_flex_child(List::new(create_batch_job_row)
+                .with_spacing(4.0)
+                .scroll()
+                .vertical()
+                .expand_width()
+                .lens(FractalData::batch_jobs), 1.0)
+            .with_spacer(8.0)
+            .with_child(Flex::row()
+                .with_flex_child(Button::new("ADD FILE").on_click(|ctx, _data: &mut FractalData, _env| {
+                    let location = FileSpec::new("location", &["toml", "kfr", "png"]);
+
+                    let open_dialog_options = FileDialogOptions::new()
+                        .allowed_types(vec![location])
+                        .accept_command(ADD_BATCH_PATH);
+
+                    ctx.submit_command(SHOW_OPEN_PANEL.with(open_dialog_options));
+                }).expand_width().fix_height(32.0), 1.0)
+                .with_spacer(4.0)
+                .with_flex_child(Button::new("ADD FOLDER").on_click(|ctx, _data: &mut FractalData, _env| {
+                    let open_dialog_options = FileDialogOptions::new()
+                        .select_directories()
+                        .accept_command(ADD_BATCH_PATH);
+
+                    ctx.submit_command(SHOW_OPEN_PANEL.with(open_dialog_options));
+                }).expand_width().fix_height(32.0), 1.0)
+                .with_spacer(4.0)
+                .with_flex_child(Button::new("CLEAR FINISHED").on_click(|ctx, _data: &mut FractalData, _env| {
+                    ctx.submit_command(Command::new(CLEAR_BATCH, (), Target::Global));
+                }).expand_width().fix_height(32.0), 1.0))
+            .with_spacer(4.0)
+            .with_child(Flex::row()
+                .with_flex_child(Button::new(|data: &FractalData, _env: &_| {
+                    if data.batch_running {
+                        "RUNNING".to_string()
+                    } else {
+                        "START".to_string()
+                    }
+                }).on_click(|ctx, _data: &mut FractalData, _env| {
+                    ctx.submit_command(Command::new(START_BATCH, (), Target::Global));
+                }).expand_width().fix_height(32.0), 1.0)
+                .with_spacer(4.0)
+                .with_flex_child(Button::new("STOP").on_click(|ctx, _data: &mut FractalData, _env| {
+                    ctx.submit_command(Command::new(STOP_BATCH, (), Target::Global));
+                }).expand_width().fix_height(32.0), 1.0)
+                .with_spacer(4.0)
+                .with_flex_child(Button::new("CLOSE").on_click(|ctx, _data: &mut FractalData, _env| {
+                    ctx.submit_command(CLOSE_WINDOW);
+                }).expand_width().fix_height(32.0), 1.0))
+            .with_spacer(8.0)
+            .cross_axis_alignment(CrossAxisAlignment::Start), 0.9)
+        .with_flex_spacer(0.05)
+}
+
+fn create_batch_job_row() -> impl Widget<BatchJob> {
+    Flex::row()
+        .with_flex_child(Label::new(|data: &BatchJob, _env: &_| {
+            data.name()
+        }).with_text_size(12.0).expand_width(), 1.0)
+        .with_spacer(4.0)
+        .with_child(Label::new(|data: &BatchJob, _env: &_| {
+            data.description()
+        }).with_text_size(12.0).with_line_break_mode(LineBreaking::WordWrap).fix_width(180.0))
+        .with_spacer(4.0)
+        .with_child(Button::new("UP").on_click(|ctx, data: &mut BatchJob, _env| {
+            ctx.submit_command(Command::new(MOVE_BATCH_JOB_UP, data.id, Target::Global));
+        }).fix_width(48.0).fix_height(24.0))
+        .with_spacer(4.0)
+        .with_child(Button::new("DOWN").on_click(|ctx, data: &mut BatchJob, _env| {
+            ctx.submit_command(Command::new(MOVE_BATCH_JOB_DOWN, data.id, Target::Global));
+        }).fix_width(48.0).fix_height(24.0))
+        .with_spacer(4.0)
+        .with_child(Button::new(|data: &BatchJob, _env: &_| {
+            if data.status.is_finished() {
+                "REMOVE".to_string()
+            } else {
+                "CANCEL".to_string()
+            }
+        }).on_click(|ctx, data: &mut BatchJob, _env| {
+            ctx.submit_command(Command::new(CANCEL_BATCH_JOB, data.id, Target::Global));
+        }).fix_width(72.0).fix_height(24.0))
+}
+
+pub fn window_palette() -> impl Widget<FractalData> {
+    let gradient = Painter::new(|ctx, data: &Palette, _env| {
+        let bounds = ctx.size().to_rect();
+        let width = bounds.width().max(1.0) as usize;
+
+        for (i, (r, g, b)) in data.samples(width).into_iter().enumerate() {
+            ctx.fill(Rect::new(i as f64, 0.0, i as f64 + 1.0, bounds.height()), &Color::rgb8(r, g, b));
+        }
+    }).fix_height(32.0).expand_width().lens(FractalData::palette);
+
+    Flex::row()
+        .with_flex_spacer(0.05)
+        .with_flex_child(Flex::column()
+            .with_spacer(8.0)
+            .with_child(gradient)
+            .with_spacer(8.0)
+            .with_child(Flex::row()
+                .with_child(Label::new("Interpolation:").with_text_size(14.0).fix_width(160.0))
+                .with_child(DropdownSelect::new(vec![
+                    ("Default", PaletteInterpolation::Default),
+                    ("Linear", PaletteInterpolation::Linear),
+                    ("Smooth", PaletteInterpolation::Smooth),
+                    ("Step", PaletteInterpolation::Step),
+                ]).lens(FractalData::palette.then(Palette::interpolation))))
+            .with_spacer(8.0)
+            .with_flex_child(List::new(create_palette_stop_row)
+                .with_spacing(8.0)
+                .scroll()
+                .vertical()
+                .lens(FractalData::palette.then(Palette::stops)), 1.0)
+            .with_spacer(8.0)
+            .with_child(Flex::row()
+                .with_flex_child(Button::new("ADD STOP").on_click(|ctx, _data: &mut FractalData, _env| {
+                    ctx.submit_command(ADD_PALETTE_STOP);
+                }).expand_width().fix_height(32.0), 1.0)
+                .with_spacer(4.0)
+                .with_flex_child(Button::new("SAVE").on_click(|ctx, _data: &mut FractalData, _env| {
+                    let toml = FileSpec::new("palette", &["toml"]);
+
+                    let save_dialog_options = FileDialogOptions::new()
+                        .allowed_types(vec![toml])
+                        .accept_command(SAVE_PALETTE);
+
+                    ctx.submit_command(SHOW_SAVE_PANEL.with(save_dialog_options));
+                }).expand_width().fix_height(32.0), 1.0)
+                .with_spacer(4.0)
+                .with_flex_child(Button::new("CLOSE").on_click(|ctx, _data: &mut FractalData, _env| {
+                    ctx.submit_command(CLOSE_WINDOW);
+                }).expand_width().fix_height(32.0), 1.0))
+            .with_spacer(8.0)
+            .cross_axis_alignment(CrossAxisAlignment::Start), 0.9)
+        .with_flex_spacer(0.05)
+        .controller(PaletteEditorController)
+}
+
+fn create_palette_stop_row() -> impl Widget<PaletteStop> {
+    let swatch = Painter::new(|ctx, data: &PaletteStop, _env| {
+        let bounds = ctx.size().to_rect();
+        ctx.fill(bounds, &Color::rgb8(data.red, data.green, data.blue));
+    }).fix_size(48.0, 48.0);
+
+    let sliders = Flex::column()
+        .with_child(Flex::row()
+            .with_child(Label::new("Position:").fix_width(80.0))
+            .with_flex_child(Slider::new()
+                .with_range(0.0, 1.0)
+                .expand_width()
+                .lens(PaletteStop::position), 1.0)
+            .with_child(Label::<f64>::new(|data: &f64, _env: &_| {
+                format!("{:>5.3}", *data)
+            }).fix_width(48.0).lens(PaletteStop::position)))
+        .with_child(create_channel_slider("Red:", PaletteStop::red))
+        .with_child(create_channel_slider("Green:", PaletteStop::green))
+        .with_child(create_channel_slider("Blue:", PaletteStop::blue));
+
+    Flex::row()
+        .with_child(swatch)
+        .with_spacer(8.0)
+        .with_flex_child(sliders, 1.0)
+        .with_spacer(4.0)
+        .with_child(Button::new("X").on_click(|ctx, data: &mut PaletteStop, _env| {
+            ctx.submit_command(DELETE_PALETTE_STOP.with(data.clone()));
+        }).fix_width(32.0).fix_height(24.0))
+}
+
+fn create_channel_slider(label: &str, lens: impl Lens<PaletteStop, u8> + Clone + 'static) -> impl Widget<PaletteStop> {
+    Flex::row()
+        .with_child(Label::new(label).fix_width(80.0))
+        .with_flex_child(Slider::new()
+            .with_range(0.0, 255.0)
+            .expand_width()
+            .lens(lens.clone().map(|val| *val as f64, |val, new| *val = new.round() as u8)), 1.0)
+        .with_child(Label::<u8>::new(|data: &u8, _env: &_| {
+            format!("{:>3}", *data)
+        }).fix_width(48.0).lens(lens))
+}
+
+fn create_notification_row() -> impl Widget<Notification> {
+    Flex::row()
+        .with_child(Label::new(|data: &Notification, _env: &_| {
+            format!("{} {:<7}", data.time, data.level.name())
+        }).with_text_size(12.0).fix_width(120.0))
+        .with_flex_child(Label::new(|data: &Notification, _env: &_| {
+            data.message.clone()
+        }).with_text_size(12.0).with_line_break_mode(LineBreaking::WordWrap).expand_width(), 1.0)
+        .cross_axis_alignment(CrossAxisAlignment::Start)
 }
\ No newline at end of file
@@ -4,7 +4,7 @@ use std::sync::Arc;
 use parking_lot::Mutex;
 
 use druid::{widget::prelude::*};
-use druid::{Widget, MouseButton, KbKey, FileDialogOptions, FileSpec, Data, Lens, Rect};
+use druid::{Widget, MouseButton, KbKey, FileDialogOptions, FileSpec, Data, Lens, Rect, WindowDesc, LocalizedString, TimerToken};
 use druid::piet::{ImageFormat, InterpolationMode, Color};
 use druid::kurbo::Circle;
 use druid::commands::{
@@ -17,24 +17,47 @@ use druid::commands::{
 use float_eq::float_eq;
 
 use rust_fractal::{renderer::FractalRenderer};
-use rust_fractal::util::{ComplexFixed, ComplexExtended, FloatExtended, FloatArbitrary, get_delta_top_left, extended_to_string_long, string_to_extended, linear_interpolation_between_zoom};
+use rust_fractal::util::{ComplexFixed, ComplexExtended, FloatExtended, get_delta_top_left, extended_to_string_long, string_to_extended, linear_interpolation_between_zoom};
 use rust_fractal::util::data_export::{DataExport, DataType, ColoringType};
 use rust_fractal::math::BoxPeriod;
 
-use config::{Config, File};
-
 use std::sync::mpsc;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::cmp::min;
+use std::time::Duration;
 
 use crate::commands::*;
-use crate::ui::FractalType;
+use crate::formats::{load_location_file, is_kfr_file, is_png_file, location_to_kfr, merge_location, apply_palette, is_palette_file, load_palette_file, PALETTE_EXTENSIONS};
+use crate::formats::{location_text_chunks, write_png_text, parse_location};
+use crate::formats::{ITERATION_MAP_EXTENSION, is_iteration_map_file, save_iteration_map, load_iteration_map};
+use crate::formats::{is_exr_file, save_exr};
+use crate::progress::{RenderProgress, RenderStage};
+use crate::history::{LocationSnapshot, NavigationHistory};
+use crate::bookmarks::{Bookmark, save_bookmarks};
+use crate::pixel::{pixel_to_location, sample_pixel, sample_loupe};
+use crate::sequence::{ZoomSequence, SequenceFormat, zoom_for_frame};
+use crate::palette::Palette;
+use crate::settings::{Settings, serialize_settings, clamp_setting, load_settings, toml_string};
+use crate::notifications::{Notification, LOG_LIMIT, TOAST_DURATION};
+use crate::ui::{FractalType, window_sequence, window_palette, window_export, window_batch};
+use crate::batch::{BatchJob, BatchStatus, batch_files_in_directory};
+use crate::export::ExportOptions;
+use crate::preview::RenderPreview;
+use crate::render_thread::{set_renderer_size, set_renderer_location};
+
+// Each notch of a mouse wheel is this large, and trackpads give fractions of it
+const WHEEL_NOTCH: f64 = 120.0;
+
+// The time after the last wheel event before the accumulated zoom is rendered
+const WHEEL_DEBOUNCE: Duration = Duration::from_millis(250);
 
 #[derive(PartialEq, Clone, Copy)]
 pub enum MouseMode {
     None,
     Panning,
-    RootFinding
+    RootFinding,
+    ZoomBox
 }
 
 pub struct FractalWidget {
@@ -47,9 +70,26 @@ pub struct FractalWidget {
     pub root_pos_current: (f64, f64),
     pub cached_image: Option<druid::piet::PietImage>,
     pub needs_buffer_refresh: bool,
+    /// Shown in place of the image until the full render of the same job repaints
+    pub preview: Option<RenderPreview>,
+    pub cached_preview: Option<druid::piet::PietImage>,
     pub mouse_mode: MouseMode,
     pub renderer_zoom: FloatExtended,
-    pub renderer_rotate: (f64, f64)
+    pub renderer_rotate: (f64, f64),
+    pub history: NavigationHistory,
+    pub root_snapshot: Option<LocationSnapshot>,
+    pub sequence: Option<ZoomSequence>,
+    pub toast_timer: TimerToken,
+    pub batch_next_id: usize,
+    pub wheel_timer: TimerToken,
+    /// The centre in image pixels and the zoom factor of the wheel zoom, kept until the new render repaints
+    pub wheel_zoom: Option<((f64, f64), f64)>,
+    /// The wheel zoom that has been sent to the renderer, until its render is shown
+    pub wheel_sent: Option<((f64, f64), f64)>,
+    /// Set when the debounce timer fired during a render, so the wheel zoom is sent once the stopped render finishes
+    pub wheel_pending: bool,
+    /// The space available to the image, used for the native size
+    pub window_size: Size,
 }
 
 #[derive(Data, Clone, Lens)]
@@ -68,12 +108,12 @@ pub struct FractalData {
     pub palette_cyclic: bool,
     pub palette_iteration_span: f64,
     pub palette_offset: f64,
+    pub palette: Palette,
     #[data(same_fn = "PartialEq::eq")]
     pub coloring_type: ColoringType,
-    pub rendering_progress: f64,
+    pub render_progress: RenderProgress,
     pub root_progress: f64,
-    pub rendering_stage: usize,
-    pub rendering_time: usize,
+    pub rendering_stage: RenderStage,
     pub root_iteration: usize,
     pub root_stage: usize,
     pub min_valid_iterations: usize,
@@ -92,9 +132,14 @@ pub struct FractalData {
     pub auto_adjust_iterations: bool,
     pub remove_centre: bool,
     pub renderer: Arc<Mutex<FractalRenderer>>,
-    pub settings: Arc<Mutex<Config>>,
-    pub sender: Arc<Mutex<mpsc::Sender<usize>>>,
+    pub settings: Arc<Mutex<Settings>>,
+    pub sender: Arc<Mutex<mpsc::Sender<ThreadCommand>>>,
+    pub render_job: usize,
     pub stop_flag: Arc<AtomicBool>,
+    pub export_sender: Arc<Mutex<mpsc::Sender<ExportJob>>>,
+    pub export_stop_flag: Arc<AtomicBool>,
+    pub batch_sender: Arc<Mutex<mpsc::Sender<BatchRender>>>,
+    pub batch_stop_flag: Arc<AtomicBool>,
     pub repeat_flag: Arc<AtomicBool>,
     pub buffer: Arc<Mutex<DataExport>>,
     pub need_full_rerender: bool,
@@ -102,6 +147,11 @@ pub struct FractalData {
     pub pixel_pos: [u32; 2],
     pub pixel_iterations: u32,
     pub pixel_smooth: f32,
+    pub pixel_distance: f32,
+    pub pixel_stripe: f32,
+    pub pixel_glitched: bool,
+    pub pixel_real: String,
+    pub pixel_imag: String,
     pub pixel_rgb: Arc<Mutex<Vec<u8>>>,
     pub mouse_mode: usize,
     pub current_tab: usize,
@@ -120,6 +170,193 @@ pub struct FractalData {
     pub lighting_diffuse: f64,
     pub lighting_specular: f64,
     pub lighting_shininess: i64,
+    pub bookmarks: Arc<Vec<Bookmark>>,
+    pub bookmark_name: String,
+    pub sequence_directory: String,
+    pub sequence_template: String,
+    pub sequence_format: SequenceFormat,
+    pub sequence_remove_centre: bool,
+    pub sequence_frames: usize,
+    pub sequence_frame_offset: usize,
+    pub export_width: usize,
+    pub export_height: usize,
+    pub export_supersampling: usize,
+    pub export_tiled: bool,
+    /// The tiles completed out of the total while an export is running
+    pub export_progress: Option<(usize, usize)>,
+    pub batch_jobs: Arc<Vec<BatchJob>>,
+    pub batch_directory: String,
+    pub batch_format: SequenceFormat,
+    pub batch_running: bool,
+    /// The ID of the job that the batch thread is rendering
+    pub batch_running_job: Option<usize>,
+    pub fractal_type: FractalType,
+    /// Keeps the zoom box at the aspect ratio of the image
+    pub zoom_box_locked: bool,
+    /// Renders a low resolution preview before each new reference
+    pub preview_enabled: bool,
+    /// Most recent first
+    pub notifications: Arc<Vec<Notification>>,
+    pub toast: Option<Notification>,
+}
+
+impl FractalWidget {
+    // Each render of a zoom sequence is saved as the next frame
+    fn next_sequence_frame(&mut self, data: &FractalData) -> Option<(usize, PathBuf)> {
+        match self.sequence.as_mut() {
+            Some(sequence) if data.zoom_out_enabled => {
+                let frame = sequence.frame;
+                sequence.frame += 1;
+
+                if sequence.is_last(frame) {
+                    data.repeat_flag.store(false, Ordering::SeqCst);
+                }
+
+                Some((frame, sequence.frame_path(frame)))
+            }
+            _ => None
+        }
+    }
+
+    // The opposite corner of the zoom box from where the drag started, widened or heightened to match the image if locked
+    fn zoom_box_corner(&self, pos: (f64, f64), size: Rect, locked: bool) -> (f64, f64) {
+        let x_delta = pos.0 - self.pos1.0;
+        let y_delta = pos.1 - self.pos1.1;
+
+        if !locked || size.height() <= 0.0 {
+            return pos;
+        }
+
+        let aspect = size.width() / size.height();
+
+        let width = x_delta.abs().max(y_delta.abs() * aspect);
+        let height = width / aspect;
+
+        (self.pos1.0 + width.copysign(x_delta), self.pos1.1 + height.copysign(y_delta))
+    }
+
+    // A wheel zoom of the image on screen, relative to the image of a wheel zoom that was sent before it
+    fn wheel_zoom_since(&self, (centre, factor): ((f64, f64), f64), (sent_centre, sent_factor): ((f64, f64), f64)) -> ((f64, f64), f64) {
+        let image_centre = (0.5 * self.image_width as f64, 0.5 * self.image_height as f64);
+
+        let centre = (
+            image_centre.0 + (centre.0 - sent_centre.0) * sent_factor,
+            image_centre.1 + (centre.1 - sent_centre.1) * sent_factor);
+
+        (centre, factor / sent_factor)
+    }
+
+    // The image of a new render is on screen, so the wheel zoom that was sent for it has been applied. Scrolling
+    // that has not been sent yet is kept relative to the new image.
+    fn wheel_render_shown(&mut self) {
+        let sent = self.wheel_sent.take();
+
+        if self.wheel_timer == TimerToken::INVALID && !self.wheel_pending {
+            self.wheel_zoom = None;
+            return;
+        }
+
+        if let (Some(wheel_zoom), Some(sent)) = (self.wheel_zoom, sent) {
+            self.wheel_zoom = Some(self.wheel_zoom_since(wheel_zoom, sent));
+        }
+    }
+
+    // Renders the location of the accumulated wheel zoom
+    fn send_wheel_zoom(&mut self, ctx: &mut EventCtx, data: &mut FractalData) {
+        self.wheel_pending = false;
+
+        let wheel_zoom = match self.wheel_zoom {
+            Some(wheel_zoom) => wheel_zoom,
+            None => return
+        };
+
+        // The renderer is already at the location of a zoom that was sent but is not shown yet
+        let (centre, factor) = match self.wheel_sent {
+            Some(sent) => self.wheel_zoom_since(wheel_zoom, sent),
+            None => wheel_zoom
+        };
+
+        // Scrolling that ended where it started, or that can no longer be applied, does not render
+        if (factor - 1.0).abs() < 1e-9 || data.root_stage == 1 {
+            if self.wheel_sent.is_none() {
+                self.wheel_zoom = None;
+            }
+
+            ctx.request_paint();
+            return;
+        }
+
+        self.wheel_sent = Some(wheel_zoom);
+
+        let image_centre = (0.5 * self.image_width as f64, 0.5 * self.image_height as f64);
+
+        // The existing reference is used if the centre has not moved
+        if (centre.0 - image_centre.0).abs() < 0.5 && (centre.1 - image_centre.1).abs() < 0.5 {
+            ctx.submit_command(MULTIPLY_ZOOM.with(factor));
+            return;
+        }
+
+        let mut settings = data.settings.lock();
+        let mut renderer = data.renderer.lock();
+
+        let (real, imag) = pixel_to_location(&renderer, centre.0, centre.1);
+
+        let mut zoom = renderer.zoom;
+
+        zoom.mantissa *= factor;
+        zoom.reduce();
+
+        data.zoom = extended_to_string_long(zoom);
+
+        self.history.push(LocationSnapshot::from_settings(&settings));
+
+        // Set the overrides for the current location
+        settings.real = real;
+        settings.imag = imag;
+        settings.zoom = data.zoom.clone();
+
+        data.real = settings.real.clone();
+        data.imag = settings.imag.clone();
+
+        renderer.adjust_iterations();
+
+        settings.iterations = renderer.maximum_iteration;
+        data.iteration_limit = renderer.maximum_iteration;
+
+        ctx.submit_command(RESET_RENDERER_FULL);
+    }
+
+    // Sends the first queued job of the batch to the render thread
+    fn next_batch_job(&mut self, ctx: &mut EventCtx, data: &mut FractalData) {
+        let directory = PathBuf::from(&data.batch_directory);
+        let format = data.batch_format;
+
+        let jobs = Arc::make_mut(&mut data.batch_jobs);
+
+        match jobs.iter().position(|job| job.status == BatchStatus::Queued) {
+            Some(index) => {
+                let job = &mut jobs[index];
+                job.status = BatchStatus::Running;
+
+                data.batch_running_job = Some(job.id);
+                data.batch_stop_flag.store(false, Ordering::SeqCst);
+
+                data.batch_sender.lock().send(BatchRender {
+                    id: job.id,
+                    location: PathBuf::from(&job.location),
+                    output: job.output_path(&directory, format),
+                }).unwrap();
+            },
+            None => {
+                let done = jobs.iter().filter(|job| job.status == BatchStatus::Done).count();
+                let failed = jobs.iter().filter(|job| job.status == BatchStatus::Failed).count();
+
+                data.batch_running = false;
+
+                ctx.submit_command(NOTIFY.with(Notification::info(format!("Batch complete: {} done, {} failed", done, failed))));
+            }
+        }
+    }
 }
 
 impl Widget<FractalData> for FractalWidget {
@@ -131,14 +368,15 @@ impl Widget<FractalData> for FractalWidget {
             Event::WindowConnected => {
                 let settings = data.settings.lock();
 
-                data.image_width = settings.get_int("image_width").unwrap() as usize;
-                data.image_height = settings.get_int("image_height").unwrap() as usize;
+                data.image_width = settings.image_width;
+                data.image_height = settings.image_height;
 
-                data.sender.lock().send(THREAD_RESET_RENDERER_FULL).unwrap();
+                data.render_job += 1;
+                data.sender.lock().send(ThreadCommand::ResetRendererFull { job: data.render_job, settings: settings.clone(), frame: None, preview: false }).unwrap();
             }
             Event::MouseMove(e) => {
                 // If the rendering / root finding has not completed, stop
-                if data.rendering_stage != 0 || data.root_stage == 1 {
+                if data.rendering_stage != RenderStage::Complete || data.root_stage == 1 {
                     return;
                 }
 
@@ -158,37 +396,50 @@ impl Widget<FractalData> for FractalWidget {
 
                         ctx.request_paint();
                     },
-                    MouseMode::None => {},
-                }
+                    MouseMode::ZoomBox => {
+                        self.pos2 = self.zoom_box_corner((e.pos.x, e.pos.y), ctx.size().to_rect(), data.zoom_box_locked);
 
-                // if data.rendering_stage == 0 {
-                //     let size = ctx.size().to_rect();
+                        ctx.request_paint();
+                    },
+                    MouseMode::None => {
+                        let size = ctx.size().to_rect();
 
-                //     let i = e.pos.x * data.image_width as f64 / size.width();
-                //     let j = e.pos.y * data.image_height as f64 / size.height();
+                        if !size.contains(e.pos) {
+                            return;
+                        }
 
-                //     let k = j as usize * data.image_width as usize + i as usize;
+                        let renderer = data.renderer.lock();
+                        let data_export = data.buffer.lock();
 
-                //     data.pixel_pos[0] = i as u32;
-                //     data.pixel_pos[1] = j as u32;
+                        let i = e.pos.x * data_export.image_width as f64 / size.width();
+                        let j = e.pos.y * data_export.image_height as f64 / size.height();
 
-                //     let data_export = data.buffer.lock();
+                        if let Some(sample) = sample_pixel(&data_export, i as usize, j as usize) {
+                            data.pixel_pos[0] = i as u32;
+                            data.pixel_pos[1] = j as u32;
 
-                //     data.pixel_iterations = data_export.iterations[k];
-                //     data.pixel_smooth = if data_export.smooth[k] <= 1.0 {
-                //         data_export.smooth[k]
-                //     } else {
-                //         0.0
-                //     };
+                            data.pixel_iterations = sample.iterations;
+                            data.pixel_smooth = sample.smooth;
+                            data.pixel_distance = sample.distance;
+                            data.pixel_stripe = sample.stripe;
+                            data.pixel_glitched = sample.glitched;
 
-                //     drop(data_export);
+                            // The renderer samples each pixel at its whole coordinate, so this is the location of the values shown
+                            let (real, imag) = pixel_to_location(&renderer, i.floor(), j.floor());
 
-                //     ctx.submit_command(UPDATE_PIXEL_INFORMATION);
-                // }
+                            data.pixel_real = real;
+                            data.pixel_imag = imag;
+
+                            *data.pixel_rgb.lock() = sample_loupe(&data_export, i as usize, j as usize);
+
+                            ctx.submit_command(UPDATE_PIXEL_INFORMATION);
+                        }
+                    },
+                }
             }
             Event::MouseDown(e) => {
                 // If the rendering has not completed, stop
-                if data.rendering_stage != 0 || data.root_stage == 1 {
+                if data.rendering_stage != RenderStage::Complete || data.root_stage == 1 {
                     return;
                 }
 
@@ -196,16 +447,16 @@ impl Widget<FractalData> for FractalWidget {
                     self.pos1 = (e.pos.x, e.pos.y);
                     self.pos2 = (e.pos.x, e.pos.y);
 
-                    self.mouse_mode = if data.mouse_mode == 2 {
-                        MouseMode::RootFinding
-                    } else {
-                        MouseMode::Panning
+                    self.mouse_mode = match data.mouse_mode {
+                        1 => MouseMode::ZoomBox,
+                        2 => MouseMode::RootFinding,
+                        _ => MouseMode::Panning
                     }
                 }
             },
             Event::MouseUp(e) => {
                 // If the rendering / root finding has not completed, stop
-                if data.rendering_stage != 0 || data.root_stage == 1 {
+                if data.rendering_stage != RenderStage::Complete || data.root_stage == 1 {
                     return;
                 }
 
@@ -227,117 +478,133 @@ impl Widget<FractalData> for FractalWidget {
                             let i = renderer.image_width as f64 / 2.0 - (self.pos2.0 - self.pos1.0) * renderer.image_width as f64 / size.width();
                             let j = renderer.image_height as f64 / 2.0 - (self.pos2.1 - self.pos1.1) * renderer.image_height as f64 / size.height();
         
-                            let cos_rotate = renderer.rotate.cos();
-                            let sin_rotate = renderer.rotate.sin();
-        
-                            let delta_pixel =  4.0 / ((renderer.image_height - 1) as f64 * renderer.zoom.mantissa);
-                            let delta_top_left = get_delta_top_left(delta_pixel, renderer.image_width, renderer.image_height, cos_rotate, sin_rotate);
-        
-                            let element = ComplexFixed::new(
-                                i * delta_pixel * cos_rotate - j * delta_pixel * sin_rotate + delta_top_left.re, 
-                                i * delta_pixel * sin_rotate + j * delta_pixel * cos_rotate + delta_top_left.im
-                            );
-        
-                            let element = ComplexExtended::new(element, -renderer.zoom.exponent);
-    
-                            let mut location = renderer.center_reference.c.clone();
-    
-                            let precision = location.real().prec();
-        
-                            let temp = FloatArbitrary::with_val(precision, element.exponent).exp2();
-                            let temp2 = FloatArbitrary::with_val(precision, element.mantissa.re);
-                            let temp3 = FloatArbitrary::with_val(precision, element.mantissa.im);
-        
-                            *location.mut_real() += &temp2 * &temp;
-                            *location.mut_imag() += &temp3 * &temp;
+                            let (real, imag) = pixel_to_location(&renderer, i, j);
+
+                            self.history.push(LocationSnapshot::from_settings(&settings));
         
                             // Set the overrides for the current location
-                            settings.set("real", location.real().to_string()).unwrap();
-                            settings.set("imag", location.imag().to_string()).unwrap();
+                            settings.real = real;
+                            settings.imag = imag;
         
-                            data.real = settings.get_str("real").unwrap();
-                            data.imag = settings.get_str("imag").unwrap();
+                            data.real = settings.real.clone();
+                            data.imag = settings.imag.clone();
 
                             self.mouse_mode = MouseMode::None;
     
                             ctx.submit_command(RESET_RENDERER_FULL);
                         },
-                        MouseMode::None => {},
-                    }
-                }
-            }
-            Event::Wheel(e) => {
-                if e.wheel_delta.y > 0.0 {
-                    ctx.submit_command(MULTIPLY_ZOOM.with(1.0 / data.zoom_scale_factor));
-                } else {
-                    // If the rendering / root finding has not completed, stop
-                    if data.rendering_stage != 0 || data.root_stage == 1 {
-                        return;
-                    }
+                        MouseMode::ZoomBox => {
+                            let size = ctx.size().to_rect();
 
-                    let mut settings = data.settings.lock();
-                    let mut renderer = data.renderer.lock();
+                            self.pos2 = self.zoom_box_corner((e.pos.x, e.pos.y), size, data.zoom_box_locked);
 
-                    let size = ctx.size().to_rect();
+                            let box_width = (self.pos2.0 - self.pos1.0).abs();
+                            let box_height = (self.pos2.1 - self.pos1.1).abs();
 
-                    let i = e.pos.x * renderer.image_width as f64 / size.width();
-                    let j = e.pos.y * renderer.image_height as f64 / size.height();
+                            // A click without dragging is not treated as a zoom
+                            if box_width < 4.0 || box_height < 4.0 {
+                                self.pos2 = self.pos1;
+                                self.mouse_mode = MouseMode::None;
 
-                    let cos_rotate = renderer.rotate.cos();
-                    let sin_rotate = renderer.rotate.sin();
+                                ctx.request_paint();
+                                return;
+                            }
 
-                    let delta_pixel =  4.0 / ((renderer.image_height - 1) as f64 * renderer.zoom.mantissa);
-                    let delta_top_left = get_delta_top_left(delta_pixel, renderer.image_width, renderer.image_height, cos_rotate, sin_rotate);
+                            let mut settings = data.settings.lock();
+                            let mut renderer = data.renderer.lock();
 
-                    let element = ComplexFixed::new(
-                        i * delta_pixel * cos_rotate - j * delta_pixel * sin_rotate + delta_top_left.re, 
-                        i * delta_pixel * sin_rotate + j * delta_pixel * cos_rotate + delta_top_left.im
-                    );
+                            // The centre of the box in image pixels
+                            let i = 0.5 * (self.pos1.0 + self.pos2.0) * renderer.image_width as f64 / size.width();
+                            let j = 0.5 * (self.pos1.1 + self.pos2.1) * renderer.image_height as f64 / size.height();
 
-                    let element = ComplexExtended::new(element, -renderer.zoom.exponent);
-                    let mut zoom = renderer.zoom;
+                            let (real, imag) = pixel_to_location(&renderer, i, j);
 
-                    zoom.mantissa *= data.zoom_scale_factor;
-                    zoom.reduce();
+                            // The whole box is kept in the frame if it has a different aspect ratio to the image
+                            let factor = (size.width() / box_width).min(size.height() / box_height);
 
-                    let mut location = renderer.center_reference.c.clone();
+                            let mut zoom = renderer.zoom;
 
-                    let precision = location.real().prec();
+                            zoom.mantissa *= factor;
+                            zoom.reduce();
 
-                    let temp = FloatArbitrary::with_val(precision, element.exponent).exp2();
-                    let temp2 = FloatArbitrary::with_val(precision, element.mantissa.re);
-                    let temp3 = FloatArbitrary::with_val(precision, element.mantissa.im);
+                            data.zoom = extended_to_string_long(zoom);
 
-                    *location.mut_real() += &temp2 * &temp;
-                    *location.mut_imag() += &temp3 * &temp;
+                            self.history.push(LocationSnapshot::from_settings(&settings));
 
-                    data.zoom = extended_to_string_long(zoom);
+                            // Set the overrides for the current location
+                            settings.real = real;
+                            settings.imag = imag;
+                            settings.zoom = data.zoom.clone();
 
-                    // Set the overrides for the current location
-                    settings.set("real", location.real().to_string()).unwrap();
-                    settings.set("imag", location.imag().to_string()).unwrap();
-                    settings.set("zoom", data.zoom.clone()).unwrap();
+                            data.real = settings.real.clone();
+                            data.imag = settings.imag.clone();
 
-                    data.real = settings.get_str("real").unwrap();
-                    data.imag = settings.get_str("imag").unwrap();
+                            renderer.adjust_iterations();
 
-                    renderer.adjust_iterations();
+                            settings.iterations = renderer.maximum_iteration;
+                            data.iteration_limit = renderer.maximum_iteration;
 
-                    settings.set("iterations", renderer.maximum_iteration as i64).unwrap();
-                    data.iteration_limit = renderer.maximum_iteration;
+                            // The box is removed rather than shifting the old image
+                            self.pos2 = self.pos1;
+                            self.mouse_mode = MouseMode::None;
 
-                    self.mouse_mode = MouseMode::None;
+                            ctx.submit_command(RESET_RENDERER_FULL);
+                        },
+                        MouseMode::None => {},
+                    }
+                }
+            }
+            Event::Wheel(e) => {
+                // Scrolling continues during a render, but not during root finding or a zoom sequence
+                if data.root_stage == 1 || data.zoom_out_enabled || self.mouse_mode != MouseMode::None {
+                    return;
+                }
 
-                    ctx.submit_command(RESET_RENDERER_FULL);
+                let size = ctx.size().to_rect();
+
+                if self.image_width == 0 || self.image_height == 0 || size.width() <= 0.0 || size.height() <= 0.0 {
+                    return;
                 }
+
+                let image_centre = (0.5 * self.image_width as f64, 0.5 * self.image_height as f64);
+                let (centre, factor) = self.wheel_zoom.unwrap_or((image_centre, 1.0));
+
+                let step = data.zoom_scale_factor.powf(-e.wheel_delta.y / WHEEL_NOTCH);
+
+                // Zooming in keeps the point under the cursor fixed, and zooming out is about the centre so that
+                // the reference can be reused
+                let new_centre = if step > 1.0 {
+                    let cursor = (
+                        centre.0 + (e.pos.x * self.image_width as f64 / size.width() - image_centre.0) / factor,
+                        centre.1 + (e.pos.y * self.image_height as f64 / size.height() - image_centre.1) / factor);
+
+                    (cursor.0 + (centre.0 - cursor.0) / step, cursor.1 + (centre.1 - cursor.1) / step)
+                } else {
+                    centre
+                };
+
+                self.wheel_zoom = Some((new_centre, factor * step));
+                self.wheel_timer = ctx.request_timer(WHEEL_DEBOUNCE);
+
+                ctx.request_paint();
             }
             Event::KeyUp(e) => {
                 // If the rendering / root finding has not completed, stop
-                if data.rendering_stage != 0 || data.root_stage == 1 {
+                if data.rendering_stage != RenderStage::Complete || data.root_stage == 1 {
                     return;
                 }
 
                 // Shortcut keys
+                if e.mods.ctrl() && (e.key == KbKey::Character("Z".to_string()) || e.key == KbKey::Character("z".to_string())) {
+                    ctx.submit_command(HISTORY_BACK);
+                    return;
+                }
+
+                if e.mods.ctrl() && (e.key == KbKey::Character("Y".to_string()) || e.key == KbKey::Character("y".to_string())) {
+                    ctx.submit_command(HISTORY_FORWARD);
+                    return;
+                }
+
                 if e.key == KbKey::Character("Z".to_string()) || e.key == KbKey::Character("z".to_string()) {
                     ctx.submit_command(MULTIPLY_ZOOM.with(2.0));
                 }
@@ -358,9 +625,17 @@ impl Widget<FractalData> for FractalWidget {
                     ctx.submit_command(MULTIPLY_SIZE.with(2.0));
                 }
 
+                if e.key == KbKey::Character("B".to_string()) || e.key == KbKey::Character("b".to_string()) {
+                    data.mouse_mode = if data.mouse_mode == 1 {
+                        0
+                    } else {
+                        1
+                    };
+                }
+
                 if e.key == KbKey::Character("R".to_string()) || e.key == KbKey::Character("r".to_string()) {
                     let settings = data.settings.lock();
-                    let new_rotate = (settings.get_float("rotate").unwrap() + 15.0) % 360.0;
+                    let new_rotate = (settings.rotate + 15.0) % 360.0;
 
                     ctx.submit_command(SET_ROTATION.with(new_rotate));
                 }
@@ -376,22 +651,64 @@ impl Widget<FractalData> for FractalWidget {
                     return;
                 }
 
+                if let Some(notification) = command.get(NOTIFY) {
+                    let notifications = Arc::make_mut(&mut data.notifications);
+
+                    notifications.insert(0, notification.clone());
+                    notifications.truncate(LOG_LIMIT);
+
+                    data.toast = Some(notification.clone());
+                    self.toast_timer = ctx.request_timer(TOAST_DURATION);
+
+                    return;
+                }
+
+                if command.is(DISMISS_NOTIFICATION) {
+                    data.toast = None;
+                    return;
+                }
+
+                if command.is(CLEAR_NOTIFICATIONS) {
+                    data.notifications = Arc::new(Vec::new());
+                    data.toast = None;
+                    return;
+                }
+
                 if let Some(period) = command.get(SET_PERIOD) {
                     data.period = *period;
 
                     return;
                 }
 
-                if let Some(root_zoom) = command.get(ROOT_FINDING_COMPLETE) {
+                if let Some((job, root)) = command.get(ROOT_FINDING_COMPLETE) {
+                    // Ignore the result of a root finding that has been replaced
+                    if *job != data.render_job {
+                        return;
+                    }
+
                     self.mouse_mode = MouseMode::None;
                     self.pos1 = self.pos2;
 
                     data.root_progress = 1.0;
                     data.root_iteration = 64;
 
-                    if let Some(root_zoom) = root_zoom {
-                        data.root_zoom = extended_to_string_long(*root_zoom);
+                    if let Some(root) = root {
+                        if let Some(snapshot) = self.root_snapshot.take() {
+                            self.history.push(snapshot);
+                        }
+
+                        let mut settings = data.settings.lock();
+
+                        settings.real = root.real.clone();
+                        settings.imag = root.imag.clone();
+                        settings.zoom = root.zoom.clone();
+
+                        data.root_zoom = extended_to_string_long(root.root_zoom);
                         data.root_stage = 0;
+
+                        // This updates the data fields to the root
+                        ctx.submit_command(REVERT_LOCATION);
+                        ctx.submit_command(RESET_RENDERER_FULL);
                     } else {
                         ctx.request_paint();
                         data.root_stage = 2;
@@ -409,38 +726,97 @@ impl Widget<FractalData> for FractalWidget {
                 }
 
                 if command.is(STOP_RENDERING) {
-                    if data.rendering_stage != 0 || data.zoom_out_enabled {
+                    if data.rendering_stage != RenderStage::Complete || data.zoom_out_enabled {
                         data.stop_flag.store(true, Ordering::SeqCst);
                     }
 
                     // if the renderer was stopped during SA / reference
-                    data.need_full_rerender = data.rendering_stage == 1 || data.rendering_stage == 2;
+                    data.need_full_rerender = matches!(data.rendering_stage, RenderStage::Reference | RenderStage::SeriesApproximation);
 
                     if data.zoom_out_enabled {
                         data.repeat_flag.store(false, Ordering::SeqCst);
                     }
 
                     data.zoom_out_enabled = false;
+                    self.sequence = None;
 
                     return;
                 }
 
-                if command.is(REPAINT) {
+                if let Some(frame) = command.get(SEQUENCE_FRAME_SAVED) {
+                    if let Some(sequence) = self.sequence.as_mut() {
+                        sequence.iterations = data.settings.lock().iterations;
+
+                        if let Err(e) = sequence.write_manifest(frame + 1) {
+                            ctx.submit_command(NOTIFY.with(Notification::error(format!("Error writing sequence manifest: {}", e))));
+                        }
+                    }
+
+                    return;
+                }
+
+                if command.is(ZOOM_OUT_COMPLETE) {
+                    data.zoom_out_enabled = false;
+                    self.sequence = None;
+
+                    return;
+                }
+
+                if let Some(job) = command.get(REPAINT) {
+                    // Ignore repaints from a render that has been replaced
+                    if *job != data.render_job {
+                        return;
+                    }
+
                     let buffer = data.buffer.lock();
 
+                    // The full render draws over the preview as it progresses
+                    self.preview = None;
+                    self.cached_preview = None;
+
                     if self.image_width != buffer.image_width || self.image_height != buffer.image_height {
                         self.image_width = buffer.image_width;
                         self.image_height = buffer.image_height;
                         ctx.request_layout();
                     }
 
+                    drop(buffer);
+
+                    self.wheel_render_shown();
+
                     self.needs_buffer_refresh = true;
                     ctx.request_paint();
 
+                    // The render that was stopped for the wheel zoom has finished, unless scrolling has restarted
+                    if self.wheel_pending && self.wheel_timer == TimerToken::INVALID && data.rendering_stage == RenderStage::Complete {
+                        self.send_wheel_zoom(ctx, data);
+                    }
+
                     return;
                 }
 
-                if let Some((iteration, progress, position)) = command.get(UPDATE_ROOT_PROGRESS) {
+                if let Some(preview) = command.get(PREVIEW_READY) {
+                    // The preview is sent before the full image is iterated
+                    if preview.job != data.render_job || !matches!(data.rendering_stage, RenderStage::Reference | RenderStage::SeriesApproximation | RenderStage::SeriesValidation) {
+                        return;
+                    }
+
+                    self.preview = Some(preview.clone());
+                    self.cached_preview = None;
+                    self.wheel_render_shown();
+
+                    // The preview is of the new location, so the panned image is no longer offset
+                    self.pos1 = self.pos2;
+
+                    ctx.request_paint();
+                    return;
+                }
+
+                if let Some((job, iteration, progress, position)) = command.get(UPDATE_ROOT_PROGRESS) {
+                    if *job != data.render_job {
+                        return;
+                    }
+
                     data.root_iteration = *iteration;
                     data.root_progress = *progress as f64 / data.period as f64;
 
@@ -459,18 +835,22 @@ impl Widget<FractalData> for FractalWidget {
                     return;
                 }
 
-                if let Some((stage, progress, time, min_valid_iterations, max_valid_iterations, reference_count)) = command.get(UPDATE_RENDERING_PROGRESS) {
-                    data.rendering_progress = *progress;
-                    data.rendering_stage = *stage;
-                    data.rendering_time = *time;
-                    data.reference_count = *reference_count;
+                if let Some(progress) = command.get(UPDATE_RENDERING_PROGRESS) {
+                    // Ignore progress from a render that has been replaced
+                    if progress.job != data.render_job {
+                        return;
+                    }
+
+                    data.render_progress = progress.clone();
+                    data.rendering_stage = progress.stage;
+                    data.reference_count = progress.reference_count;
 
-                    if *stage >= 3 || *stage == 0 {
-                        data.min_valid_iterations = *min_valid_iterations;
-                        data.max_valid_iterations = *max_valid_iterations;
+                    if !matches!(progress.stage, RenderStage::Reference | RenderStage::SeriesApproximation) {
+                        data.min_valid_iterations = progress.min_valid_iteration;
+                        data.max_valid_iterations = progress.max_valid_iteration;
                     }
 
-                    if *stage == 0 {
+                    if progress.stage == RenderStage::Complete {
                         let temp = *data.buffer.lock().iterations.iter().min().unwrap() as usize;
 
                         data.min_iterations = if temp != 0xFFFFFFFF {
@@ -485,8 +865,291 @@ impl Widget<FractalData> for FractalWidget {
                     return;
                 }
 
+                // The palette is applied to the shared buffer, so changes from the editor are shown during a render as well
+                if command.is(PREVIEW_PALETTE) {
+                    let settings = data.settings.lock();
+                    let mut data_export = data.buffer.lock();
+
+                    apply_palette(&mut data_export, &settings, &data.palette);
+                    data_export.regenerate();
+
+                    data.palette_source = "editor".to_string();
+
+                    ctx.submit_command(UPDATE_PALETTE);
+                    ctx.submit_command(REPAINT.with(data.render_job));
+                    return;
+                }
+
+                if command.is(OPEN_PALETTE_EDITOR) {
+                    // Start from the current palette of the renderer if no stops have been loaded
+                    if data.palette.stops.is_empty() {
+                        data.palette = Palette::from_data_export(&data.buffer.lock());
+                    }
+
+                    ctx.new_window(WindowDesc::new(window_palette()).title(
+                        LocalizedString::new("Palette Editor"),
+                    ).window_size((600.0, 600.0)).resizable(true));
+
+                    return;
+                }
+
+                if let Some(bookmark) = command.get(DELETE_BOOKMARK) {
+                    Arc::make_mut(&mut data.bookmarks).retain(|other| other != bookmark);
+
+                    if let Err(e) = save_bookmarks(&data.bookmarks) {
+                        ctx.submit_command(NOTIFY.with(Notification::error(format!("Error saving bookmarks: {}", e))));
+                    }
+
+                    return;
+                }
+
+                if command.is(OPEN_EXPORT_WINDOW) {
+                    ctx.new_window(WindowDesc::new(window_export()).title(
+                        LocalizedString::new("Export"),
+                    ).window_size((400.0, 300.0)).resizable(true));
+
+                    return;
+                }
+
+                if command.is(START_EXPORT) {
+                    if data.export_progress.is_some() {
+                        ctx.submit_command(NOTIFY.with(Notification::warning("An export is already running")));
+                        return;
+                    }
+
+                    let png = FileSpec::new("Portable Network Graphics", &["png"]);
+                    let jpg = FileSpec::new("JPEG", &["jpg"]);
+
+                    // Tiles are streamed into the PNG encoder
+                    let allowed_types = if data.export_tiled {
+                        vec![png]
+                    } else {
+                        vec![png, jpg]
+                    };
+
+                    let save_dialog_options = FileDialogOptions::new()
+                        .allowed_types(allowed_types)
+                        .accept_command(SAVE_EXPORT);
+
+                    ctx.submit_command(SHOW_SAVE_PANEL.with(save_dialog_options));
+                    return;
+                }
+
+                if let Some(progress) = command.get(EXPORT_PROGRESS) {
+                    data.export_progress = *progress;
+                    return;
+                }
+
+                if command.is(STOP_EXPORT) {
+                    if data.export_progress.is_some() {
+                        data.export_stop_flag.store(true, Ordering::SeqCst);
+                    }
+
+                    return;
+                }
+
+                if let Some(fractal_type) = command.get(SET_FRACTAL_TYPE) {
+                    let mut settings = data.settings.lock();
+
+                    if FractalType::from_settings(&settings) == *fractal_type {
+                        return;
+                    }
+
+                    // The formula can only be changed between renders
+                    if data.rendering_stage != RenderStage::Complete || data.root_stage == 1 {
+                        data.fractal_type = FractalType::from_settings(&settings);
+                        return;
+                    }
+
+                    fractal_type.apply(&mut settings);
+
+                    if !fractal_type.supports_root_finding() {
+                        data.mouse_mode = 0;
+                    }
+
+                    ctx.submit_command(RESET_RENDERER_FULL);
+                    return;
+                }
+
+                if command.is(OPEN_BATCH_WINDOW) {
+                    ctx.new_window(WindowDesc::new(window_batch()).title(
+                        LocalizedString::new("Batch Render"),
+                    ).window_size((640.0, 480.0)).resizable(true));
+
+                    return;
+                }
+
+                if let Some(file_info) = command.get(SELECT_BATCH_DIRECTORY) {
+                    data.batch_directory = file_info.path().display().to_string();
+                    return;
+                }
+
+                if let Some(file_info) = command.get(ADD_BATCH_PATH) {
+                    let paths = if file_info.path().is_dir() {
+                        match batch_files_in_directory(file_info.path()) {
+                            Ok(paths) => paths,
+                            Err(e) => {
+                                ctx.submit_command(NOTIFY.with(Notification::error(format!("Error reading {}: {}", file_info.path().display(), e))));
+                                return;
+                            }
+                        }
+                    } else {
+                        vec![file_info.path().to_path_buf()]
+                    };
+
+                    let jobs = Arc::make_mut(&mut data.batch_jobs);
+
+                    for path in paths {
+                        jobs.push(BatchJob::new(self.batch_next_id, &path));
+                        self.batch_next_id += 1;
+                    }
+
+                    return;
+                }
+
+                if command.is(START_BATCH) {
+                    if data.batch_running {
+                        return;
+                    }
+
+                    // A stopped job has to finish before the next one can use the stop flag
+                    if data.batch_running_job.is_some() {
+                        ctx.submit_command(NOTIFY.with(Notification::warning("The stopped batch job is still finishing")));
+                        return;
+                    }
+
+                    if let Err(e) = std::fs::create_dir_all(&data.batch_directory) {
+                        ctx.submit_command(NOTIFY.with(Notification::error(format!("Error creating batch directory: {}", e))));
+                        return;
+                    }
+
+                    data.batch_running = true;
+                    self.next_batch_job(ctx, data);
+
+                    return;
+                }
+
+                if command.is(STOP_BATCH) {
+                    data.batch_running = false;
+
+                    // The job that is running is cancelled, and the rest stay queued
+                    if let Some(job) = Arc::make_mut(&mut data.batch_jobs).iter_mut().find(|job| job.status == BatchStatus::Running) {
+                        job.status = BatchStatus::Cancelled;
+                        data.batch_stop_flag.store(true, Ordering::SeqCst);
+                    }
+
+                    return;
+                }
+
+                if command.is(CLEAR_BATCH) {
+                    Arc::make_mut(&mut data.batch_jobs).retain(|job| !job.status.is_finished());
+                    return;
+                }
+
+                if let Some(id) = command.get(MOVE_BATCH_JOB_UP) {
+                    let jobs = Arc::make_mut(&mut data.batch_jobs);
+
+                    if let Some(index) = jobs.iter().position(|job| job.id == *id) {
+                        if index > 0 {
+                            jobs.swap(index, index - 1);
+                        }
+                    }
+
+                    return;
+                }
+
+                if let Some(id) = command.get(MOVE_BATCH_JOB_DOWN) {
+                    let jobs = Arc::make_mut(&mut data.batch_jobs);
+
+                    if let Some(index) = jobs.iter().position(|job| job.id == *id) {
+                        if index + 1 < jobs.len() {
+                            jobs.swap(index, index + 1);
+                        }
+                    }
+
+                    return;
+                }
+
+                if let Some(id) = command.get(CANCEL_BATCH_JOB) {
+                    let jobs = Arc::make_mut(&mut data.batch_jobs);
+
+                    if let Some(index) = jobs.iter().position(|job| job.id == *id) {
+                        match jobs[index].status {
+                            BatchStatus::Queued => jobs[index].status = BatchStatus::Cancelled,
+                            BatchStatus::Running => {
+                                jobs[index].status = BatchStatus::Cancelled;
+                                data.batch_stop_flag.store(true, Ordering::SeqCst);
+                            },
+                            _ => {
+                                jobs.remove(index);
+                            }
+                        }
+                    }
+
+                    return;
+                }
+
+                if let Some((id, progress)) = command.get(BATCH_PROGRESS) {
+                    if let Some(job) = Arc::make_mut(&mut data.batch_jobs).iter_mut().find(|job| job.id == *id && job.status == BatchStatus::Running) {
+                        job.progress = progress.clone();
+                    }
+
+                    return;
+                }
+
+                if let Some((id, result)) = command.get(BATCH_JOB_COMPLETE) {
+                    // Only the job that was sent last moves the queue on
+                    if data.batch_running_job != Some(*id) {
+                        return;
+                    }
+
+                    data.batch_running_job = None;
+
+                    // Cancelled jobs keep their status
+                    if let Some(job) = Arc::make_mut(&mut data.batch_jobs).iter_mut().find(|job| job.id == *id && job.status == BatchStatus::Running) {
+                        match result {
+                            Ok(()) => job.status = BatchStatus::Done,
+                            Err(e) => {
+                                job.status = BatchStatus::Failed;
+                                job.error = e.clone();
+                            }
+                        }
+                    }
+
+                    if data.batch_running {
+                        self.next_batch_job(ctx, data);
+                    }
+
+                    return;
+                }
+
+                // Exports use their own renderer and thread, so they do not need to wait for the preview
+                if let Some(file_info) = command.get(SAVE_EXPORT) {
+                    let options = ExportOptions {
+                        width: data.export_width,
+                        height: data.export_height,
+                        supersampling: data.export_supersampling,
+                        tiled: data.export_tiled,
+                        path: file_info.path().to_path_buf(),
+                    };
+
+                    ctx.submit_command(NOTIFY.with(Notification::info(format!(
+                        "Exporting {}x{} with {}x supersampling", options.width, options.height, options.supersampling))));
+
+                    data.export_progress = Some((0, 1));
+                    data.export_stop_flag.store(false, Ordering::SeqCst);
+
+                    data.export_sender.lock().send(ExportJob {
+                        settings: data.settings.lock().clone(),
+                        palette: data.palette.clone(),
+                        options,
+                    }).unwrap();
+
+                    return;
+                }
+
                 // If the rendering / root finding has not completed, stop
-                if data.rendering_stage != 0 || data.root_stage == 1 {
+                if data.rendering_stage != RenderStage::Complete || data.root_stage == 1 {
                     return;
                 }
 
@@ -494,16 +1157,16 @@ impl Widget<FractalData> for FractalWidget {
                 let mut renderer = data.renderer.lock();
 
                 if let Some(factor) = command.get(MULTIPLY_SIZE) {
-                    let new_width = settings.get_int("image_width").unwrap() as f64 * factor;
-                    let new_height = settings.get_int("image_height").unwrap() as f64 * factor;
+                    let new_width = settings.image_width as f64 * factor;
+                    let new_height = settings.image_height as f64 * factor;
 
                     ctx.submit_command(SET_SIZE.with((new_width as usize, new_height as usize)));
                     return;
                 }
 
                 if command.is(NATIVE_SIZE) {
-                    data.image_width = settings.get_float("window_width").unwrap() as usize;
-                    data.image_height = settings.get_float("window_height").unwrap() as usize;
+                    data.image_width = self.window_size.width as usize;
+                    data.image_height = self.window_size.height as usize;
                     return;
                 }
 
@@ -512,23 +1175,10 @@ impl Widget<FractalData> for FractalWidget {
                         return;
                     }
 
-                    settings.set("image_width", dimensions.0 as i64).unwrap();
-                    settings.set("image_height", dimensions.1 as i64).unwrap();
-
-                    renderer.image_width = dimensions.0 as usize;
-                    renderer.image_height = dimensions.1 as usize;
-
-                    renderer.total_pixels = renderer.image_width * renderer.image_height;
+                    settings.image_width = dimensions.0;
+                    settings.image_height = dimensions.1;
 
-                    if renderer.remove_centre {
-                        let temp = 1.0 / renderer.zoom_scale_factor;
-
-                        // Add one to avoid rescaling artifacts
-                        let val1 = (renderer.image_width as f64 * temp).ceil() as usize - 1;
-                        let val2 = (renderer.image_height as f64 * temp).ceil() as usize - 1;
-                
-                        renderer.total_pixels -= val1 * val2;
-                    }
+                    set_renderer_size(&mut renderer, dimensions.0, dimensions.1);
 
                     ctx.submit_command(RESET_RENDERER_FAST);
                     return;
@@ -541,14 +1191,14 @@ impl Widget<FractalData> for FractalWidget {
                         return;
                     }
 
-                    settings.set("iterations", *iterations as i64).unwrap();
+                    settings.iterations = *iterations;
                     data.iteration_limit = *iterations;
 
                     if *iterations as usize <= renderer.maximum_iteration {
                         renderer.data_export.lock().maximum_iteration = data.iteration_limit as usize;
                         renderer.data_export.lock().regenerate();
 
-                        ctx.submit_command(REPAINT);
+                        ctx.submit_command(REPAINT.with(data.render_job));
                         return;
                     }
 
@@ -563,13 +1213,8 @@ impl Widget<FractalData> for FractalWidget {
                     // These options require the entire renderer to be refreshed
                     if renderer.center_reference.data_storage_interval != data.iteration_interval as usize ||
                         !float_eq!(renderer.center_reference.glitch_tolerance, data.glitch_tolerance, ulps <= 4) {
-                        if data.iteration_interval < 1 {
-                            data.iteration_interval = 1;
-                        }
-
-                        if data.glitch_tolerance < 0.0 {
-                            data.glitch_tolerance = 0.0;
-                        }
+                        data.iteration_interval = clamp_setting("data_storage_interval", data.iteration_interval as f64) as i64;
+                        data.glitch_tolerance = clamp_setting("glitch_tolerance", data.glitch_tolerance);
 
                         refresh_type = 1;
                     } else if renderer.series_approximation.order != data.order as usize ||
@@ -577,17 +1222,8 @@ impl Widget<FractalData> for FractalWidget {
                         renderer.series_approximation.tiled != data.series_approximation_tiled || 
                         renderer.series_approximation.enabled != data.series_approximation_enabled {
 
-                        if (data.order as usize) > 128 {
-                            data.order = 128;
-                        } else if (data.order as usize) < 4 {
-                            data.order = 4;
-                        }
-
-                        if (data.probe_sampling as usize) > 128 {
-                            data.probe_sampling = 128;
-                        } else if (data.probe_sampling as usize) < 2 {
-                            data.probe_sampling = 2;
-                        }
+                        data.order = clamp_setting("approximation_order", data.order as f64) as usize;
+                        data.probe_sampling = clamp_setting("probe_sampling", data.probe_sampling as f64) as i64;
 
                         renderer.progress.reset_series_approximation();
 
@@ -597,21 +1233,8 @@ impl Widget<FractalData> for FractalWidget {
                         renderer.remove_centre != data.remove_centre ||
                         (renderer.jitter && !float_eq!(renderer.jitter_factor, data.jitter_factor, ulps <= 4)) {
 
-                        if data.glitch_percentage > 100.0 {
-                            data.glitch_percentage = 100.0;
-                        }
-    
-                        if data.glitch_percentage < 0.0 {
-                            data.glitch_percentage = 0.0;
-                        }
-
-                        if data.jitter_factor > 100.0 {
-                            data.glitch_percentage = 100.0;
-                        }
-    
-                        if data.jitter_factor < 0.0 {
-                            data.glitch_percentage = 0.0;
-                        }
+                        data.glitch_percentage = clamp_setting("glitch_percentage", data.glitch_percentage);
+                        data.jitter_factor = clamp_setting("jitter_factor", data.jitter_factor);
 
                         if data.remove_centre {
                             renderer.data_export.lock().clear_buffers();
@@ -625,20 +1248,20 @@ impl Widget<FractalData> for FractalWidget {
                     };
 
                     // set all the config to be updated
-                    settings.set("data_storage_interval", data.iteration_interval).unwrap();
-                    settings.set("glitch_tolerance", data.glitch_tolerance).unwrap();
-                    settings.set("approximation_order", data.order as i64).unwrap();
-                    settings.set("probe_sampling", data.probe_sampling).unwrap();
+                    settings.data_storage_interval = data.iteration_interval as usize;
+                    settings.glitch_tolerance = data.glitch_tolerance;
+                    settings.approximation_order = data.order;
+                    settings.probe_sampling = data.probe_sampling as usize;
 
-                    settings.set("series_approximation_tiled", data.series_approximation_tiled).unwrap();
-                    settings.set("series_approximation_enabled", data.series_approximation_enabled).unwrap();
+                    settings.series_approximation_tiled = data.series_approximation_tiled;
+                    settings.series_approximation_enabled = data.series_approximation_enabled;
 
-                    settings.set("glitch_percentage", data.glitch_percentage).unwrap();
-                    settings.set("jitter", data.jitter).unwrap();
-                    settings.set("jitter_factor", data.jitter_factor).unwrap();
-                    settings.set("remove_centre", data.remove_centre).unwrap();
-                    settings.set("display_glitches", data.display_glitches).unwrap();
-                    settings.set("auto_adjust_iterations", data.auto_adjust_iterations).unwrap();
+                    settings.glitch_percentage = data.glitch_percentage;
+                    settings.jitter = data.jitter;
+                    settings.jitter_factor = data.jitter_factor;
+                    settings.remove_centre = data.remove_centre;
+                    settings.display_glitches = data.display_glitches;
+                    settings.auto_adjust_iterations = data.auto_adjust_iterations;
 
                     renderer.center_reference.data_storage_interval = data.iteration_interval as usize;
                     renderer.center_reference.glitch_tolerance = data.glitch_tolerance;
@@ -653,46 +1276,131 @@ impl Widget<FractalData> for FractalWidget {
                     renderer.jitter = data.jitter;
                     renderer.jitter_factor = data.jitter_factor;
 
-                    renderer.zoom_scale_factor = data.zoom_scale_factor;
-                    renderer.remove_centre = data.remove_centre;
+                    renderer.zoom_scale_factor = data.zoom_scale_factor;
+                    renderer.remove_centre = data.remove_centre;
+
+                    renderer.data_export.lock().display_glitches = data.display_glitches;
+
+                    renderer.auto_adjust_iterations = data.auto_adjust_iterations;
+
+                    match refresh_type {
+                        1 => {
+                            ctx.submit_command(RESET_RENDERER_FULL);
+                        }
+                        2 => {
+                            ctx.submit_command(RESET_RENDERER_FAST);
+                        }
+                        3 => {
+                            ctx.submit_command(REPAINT.with(data.render_job));
+                        }
+                        _ => {}
+                    }
+
+                    return;
+                }
+
+                if command.is(ADD_BOOKMARK) {
+                    let name = if data.bookmark_name.trim().is_empty() {
+                        format!("Bookmark {}", data.bookmarks.len() + 1)
+                    } else {
+                        data.bookmark_name.trim().to_string()
+                    };
+
+                    let bookmark = Bookmark::new(name, &settings, data.period, &renderer.data_export.lock());
+
+                    Arc::make_mut(&mut data.bookmarks).push(bookmark);
+                    data.bookmark_name = String::new();
+
+                    if let Err(e) = save_bookmarks(&data.bookmarks) {
+                        ctx.submit_command(NOTIFY.with(Notification::error(format!("Error saving bookmarks: {}", e))));
+                    }
+
+                    return;
+                }
+
+                if let Some(bookmark) = command.get(GO_TO_BOOKMARK) {
+                    data.real = bookmark.real.clone();
+                    data.imag = bookmark.imag.clone();
+                    data.zoom = bookmark.zoom.to_uppercase();
+                    data.iteration_limit = bookmark.iterations;
+                    data.rotation = bookmark.rotation;
+                    data.period = bookmark.period;
+
+                    // A different formula always needs a new reference
+                    if !settings.fractal_type.eq_ignore_ascii_case(&bookmark.fractal_type) || settings.fractal_power != bookmark.fractal_power {
+                        self.history.push(LocationSnapshot::from_settings(&settings));
+
+                        settings.real = data.real.clone();
+                        settings.imag = data.imag.clone();
+                        settings.zoom = data.zoom.clone();
+                        settings.rotate = data.rotation;
+                        settings.iterations = data.iteration_limit;
+                        settings.fractal_type = bookmark.fractal_type.clone();
+                        settings.fractal_power = bookmark.fractal_power;
+
+                        data.fractal_type = FractalType::from_settings(&settings);
+                        data.fractal_type.apply(&mut settings);
+
+                        if !data.fractal_type.supports_root_finding() {
+                            data.mouse_mode = 0;
+                        }
+
+                        ctx.submit_command(RESET_RENDERER_FULL);
+                        return;
+                    }
+
+                    ctx.submit_command(SET_LOCATION);
+                    return;
+                }
+
+                if command.is(HISTORY_BACK) || command.is(HISTORY_FORWARD) {
+                    let current = LocationSnapshot::from_settings(&settings);
+
+                    let snapshot = if command.is(HISTORY_BACK) {
+                        self.history.back(current)
+                    } else {
+                        self.history.forward(current)
+                    };
 
-                    renderer.data_export.lock().display_glitches = data.display_glitches;
+                    if let Some(snapshot) = snapshot {
+                        snapshot.apply(&mut settings);
 
-                    renderer.auto_adjust_iterations = data.auto_adjust_iterations;
+                        data.real = snapshot.real;
+                        data.imag = snapshot.imag;
+                        data.zoom = snapshot.zoom.to_uppercase();
+                        data.iteration_limit = snapshot.iterations;
+                        data.rotation = snapshot.rotation;
+                        data.fractal_type = FractalType::from_settings(&settings);
 
-                    match refresh_type {
-                        1 => {
-                            ctx.submit_command(RESET_RENDERER_FULL);
-                        }
-                        2 => {
-                            ctx.submit_command(RESET_RENDERER_FAST);
+                        if !data.fractal_type.supports_root_finding() {
+                            data.mouse_mode = 0;
                         }
-                        3 => {
-                            ctx.submit_command(REPAINT);
-                        }
-                        _ => {}
+
+                        ctx.submit_command(RESET_RENDERER_FULL);
                     }
 
                     return;
                 }
 
                 if command.is(SET_LOCATION) {
-                    let current_real = settings.get_str("real").unwrap();
-                    let current_imag = settings.get_str("imag").unwrap();
-                    let current_zoom = settings.get_str("zoom").unwrap();
+                    let current_real = settings.real.clone();
+                    let current_imag = settings.imag.clone();
+                    let current_zoom = settings.zoom.clone();
+
+                    let current_iterations = settings.iterations;
+                    let current_rotation = settings.rotate;
+
+                    // nothing has changed
+                    if current_real == data.real && current_imag == data.imag && current_zoom.to_uppercase() == data.zoom.to_uppercase() && 
+                        float_eq!(current_rotation, data.rotation, ulps <= 4) && current_iterations == data.iteration_limit {
+                        return;
+                    }
 
-                    let current_iterations = settings.get_int("iterations").unwrap() as usize;
-                    let current_rotation = settings.get_float("rotate").unwrap();
+                    self.history.push(LocationSnapshot::from_settings(&settings));
 
                     if current_real == data.real && current_imag == data.imag {
                         // Check if the zoom has decreased or is near to the current level
                         if current_zoom.to_uppercase() == data.zoom.to_uppercase() {
-                            // nothing has changed
-                            if float_eq!(current_rotation, data.rotation, ulps <= 4) && current_iterations == data.iteration_limit {
-                                // println!("nothing");
-                                return;
-                            }
-
                             // iterations changed
                             if current_iterations == data.iteration_limit {
                                 // println!("rotation");
@@ -708,7 +1416,7 @@ impl Widget<FractalData> for FractalWidget {
 
                             // println!("rotation & iterations");
 
-                            settings.set("iterations", data.iteration_limit as i64).unwrap();
+                            settings.iterations = data.iteration_limit;
 
                             if (data.iteration_limit as usize) < renderer.maximum_iteration {
                                 // TODO needs to make it so that pixels are only iterated to the right level
@@ -725,7 +1433,7 @@ impl Widget<FractalData> for FractalWidget {
                             if new_zoom.exponent <= current_exponent {
                                 // println!("zoom decreased");
                                 renderer.zoom = new_zoom;
-                                settings.set("zoom", data.zoom.clone()).unwrap();
+                                settings.zoom = data.zoom.clone();
 
                                 ctx.submit_command(RESET_RENDERER_FAST);
                                 return;
@@ -735,11 +1443,11 @@ impl Widget<FractalData> for FractalWidget {
 
                     // println!("location changed / zoom increased / iterations increased and rotation");
 
-                    settings.set("real", data.real.clone()).unwrap();
-                    settings.set("imag", data.imag.clone()).unwrap();
-                    settings.set("zoom",  data.zoom.clone()).unwrap();
-                    settings.set("rotate", data.rotation).unwrap();
-                    settings.set("iterations", data.iteration_limit as i64).unwrap();
+                    settings.real = data.real.clone();
+                    settings.imag = data.imag.clone();
+                    settings.zoom = data.zoom.clone();
+                    settings.rotate = data.rotation;
+                    settings.iterations = data.iteration_limit;
 
                     ctx.submit_command(RESET_RENDERER_FULL);
                     return;
@@ -747,22 +1455,24 @@ impl Widget<FractalData> for FractalWidget {
 
                 // TODO maybe enable the iterations and rotation parts
                 if command.is(REVERT_LOCATION) {
-                    data.real = settings.get_str("real").unwrap();
-                    data.imag = settings.get_str("imag").unwrap();
-                    data.zoom = settings.get_str("zoom").unwrap();
-                    data.iteration_limit = settings.get_int("iterations").unwrap() as usize;
+                    data.real = settings.real.clone();
+                    data.imag = settings.imag.clone();
+                    data.zoom = settings.zoom.clone();
+                    data.iteration_limit = settings.iterations;
 
-                    // let current_rotation = settings.get_float("rotate").unwrap();
+                    // let current_rotation = settings.rotate;
                 }
 
                 if let Some(factor) = command.get(MULTIPLY_PATTERN) {
                     let new_zoom = linear_interpolation_between_zoom(renderer.zoom, string_to_extended(&data.root_zoom), *factor);
 
                     if new_zoom.exponent > -1 {
+                        self.history.push(LocationSnapshot::from_settings(&settings));
+
                         renderer.zoom = new_zoom;
 
                         data.zoom = extended_to_string_long(renderer.zoom);
-                        settings.set("zoom", data.zoom.clone()).unwrap();
+                        settings.zoom = data.zoom.clone();
 
                         if string_to_extended(&data.zoom) > string_to_extended(&data.center_reference_zoom) {
                             data.need_full_rerender = true;
@@ -775,15 +1485,20 @@ impl Widget<FractalData> for FractalWidget {
                 }
 
                 if let Some(factor) = command.get(MULTIPLY_ZOOM) {
+                    // Each frame of a zoom out sequence should not be recorded
+                    if !data.zoom_out_enabled {
+                        self.history.push(LocationSnapshot::from_settings(&settings));
+                    }
+
                     renderer.zoom.mantissa *= factor;
                     renderer.zoom.reduce();
 
                     data.zoom = extended_to_string_long(renderer.zoom);
-                    settings.set("zoom", data.zoom.clone()).unwrap();
+                    settings.zoom = data.zoom.clone();
 
                     data.need_full_rerender &= renderer.adjust_iterations();
 
-                    settings.set("iterations", renderer.maximum_iteration as i64).unwrap();
+                    settings.iterations = renderer.maximum_iteration;
                     data.iteration_limit = renderer.maximum_iteration;
 
                     if string_to_extended(&data.zoom) > string_to_extended(&data.center_reference_zoom) {
@@ -804,6 +1519,172 @@ impl Widget<FractalData> for FractalWidget {
                     return;
                 }
 
+                if command.is(OPEN_SEQUENCE_WINDOW) {
+                    ctx.new_window(WindowDesc::new(window_sequence()).title(
+                        LocalizedString::new("Zoom Sequence"),
+                    ).window_size((600.0, 360.0)).resizable(true));
+
+                    return;
+                }
+
+                if command.is(START_SEQUENCE) {
+                    let directory = PathBuf::from(&data.sequence_directory);
+
+                    if let Err(e) = std::fs::create_dir_all(&directory) {
+                        ctx.submit_command(NOTIFY.with(Notification::error(format!("Error creating sequence directory: {}", e))));
+                        return;
+                    }
+
+                    settings.frames = data.sequence_frames;
+                    settings.frame_offset = data.sequence_frame_offset;
+
+                    if data.sequence_remove_centre {
+                        renderer.data_export.lock().centre_removed = false;
+                        renderer.data_export.lock().clear_buffers();
+                    }
+
+                    renderer.remove_centre = data.sequence_remove_centre;
+                    renderer.zoom_scale_factor = data.zoom_scale_factor;
+
+                    data.remove_centre = data.sequence_remove_centre;
+                    settings.remove_centre = data.remove_centre;
+
+                    self.history.push(LocationSnapshot::from_settings(&settings));
+
+                    let sequence = ZoomSequence::new(
+                        directory, 
+                        &settings,
+                        &data.palette,
+                        data.sequence_template.clone(), 
+                        data.sequence_format, 
+                        data.zoom_scale_factor, 
+                        data.sequence_frame_offset, 
+                        data.sequence_frames);
+
+                    if let Err(e) = sequence.write_manifest(sequence.frame) {
+                        ctx.submit_command(NOTIFY.with(Notification::error(format!("Error writing sequence manifest: {}", e))));
+                    }
+
+                    // Skip to the zoom of the first frame
+                    renderer.zoom = zoom_for_frame(renderer.zoom, data.zoom_scale_factor, data.sequence_frame_offset);
+
+                    data.zoom = extended_to_string_long(renderer.zoom);
+                    settings.zoom = data.zoom.clone();
+
+                    renderer.adjust_iterations();
+
+                    settings.iterations = renderer.maximum_iteration;
+                    data.iteration_limit = renderer.maximum_iteration;
+
+                    self.sequence = Some(sequence);
+
+                    data.zoom_out_enabled = true;
+                    data.repeat_flag.store(true, Ordering::SeqCst);
+
+                    ctx.submit_command(RESET_RENDERER_FAST);
+                    return;
+                }
+
+                if command.is(OPEN_SEQUENCE_MANIFEST) {
+                    let toml = FileSpec::new("sequence manifest", &["toml"]);
+
+                    let open_dialog_options = FileDialogOptions::new()
+                        .allowed_types(vec![toml])
+                        .accept_command(RESUME_SEQUENCE);
+
+                    ctx.submit_command(SHOW_OPEN_PANEL.with(open_dialog_options));
+                    return;
+                }
+
+                if let Some(file_info) = command.get(RESUME_SEQUENCE) {
+                    let sequence = match ZoomSequence::from_manifest(file_info.path()) {
+                        Ok(sequence) => sequence,
+                        Err(e) => {
+                            ctx.submit_command(NOTIFY.with(Notification::error(format!("Error reading sequence manifest: {}", e))));
+                            return;
+                        }
+                    };
+
+                    let zoom = zoom_for_frame(string_to_extended(&sequence.zoom), sequence.zoom_scale_factor, sequence.frame);
+
+                    if sequence.is_complete() || zoom.to_float() <= 0.5 {
+                        ctx.submit_command(NOTIFY.with(Notification::info("Sequence is already complete")));
+                        return;
+                    }
+
+                    self.history.push(LocationSnapshot::from_settings(&settings));
+
+                    settings.real = sequence.real.clone();
+                    settings.imag = sequence.imag.clone();
+                    settings.zoom = extended_to_string_long(zoom);
+                    settings.rotate = sequence.rotate;
+                    settings.iterations = sequence.iterations;
+                    settings.image_width = sequence.image_width;
+                    settings.image_height = sequence.image_height;
+                    settings.remove_centre = sequence.remove_centre;
+                    settings.zoom_scale = sequence.zoom_scale_factor;
+                    settings.fractal_type = sequence.fractal_type.clone();
+                    settings.fractal_power = sequence.fractal_power;
+
+                    data.fractal_type = FractalType::from_settings(&settings);
+                    data.fractal_type.apply(&mut settings);
+
+                    if !data.fractal_type.supports_root_finding() {
+                        data.mouse_mode = 0;
+                    }
+
+                    // The remaining frames are coloured in the same way as the ones already saved
+                    if let Some(palette) = &sequence.palette {
+                        settings.palette_iteration_span = sequence.palette_iteration_span;
+                        settings.palette_offset = sequence.palette_offset;
+                        settings.palette_cyclic = sequence.palette_cyclic;
+
+                        data.palette_iteration_span = sequence.palette_iteration_span;
+                        data.palette_offset = sequence.palette_offset;
+                        data.palette_cyclic = sequence.palette_cyclic;
+
+                        apply_palette(&mut renderer.data_export.lock(), &settings, palette);
+
+                        data.palette = palette.clone();
+                        data.palette_source = file_info.path().file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+
+                        ctx.submit_command(UPDATE_PALETTE);
+                    }
+
+                    // The centre of the previous frame is not available, so the first frame is always rendered in full
+                    if sequence.remove_centre {
+                        renderer.data_export.lock().centre_removed = false;
+                        renderer.data_export.lock().clear_buffers();
+                    }
+
+                    renderer.remove_centre = sequence.remove_centre;
+                    renderer.zoom_scale_factor = sequence.zoom_scale_factor;
+
+                    data.real = sequence.real.clone();
+                    data.imag = sequence.imag.clone();
+                    data.zoom = settings.zoom.to_uppercase();
+                    data.rotation = sequence.rotate;
+                    data.iteration_limit = sequence.iterations;
+                    data.image_width = sequence.image_width;
+                    data.image_height = sequence.image_height;
+                    data.remove_centre = sequence.remove_centre;
+                    data.zoom_scale_factor = sequence.zoom_scale_factor;
+
+                    data.sequence_directory = sequence.directory.to_string_lossy().to_string();
+                    data.sequence_template = sequence.template.clone();
+                    data.sequence_format = sequence.format;
+                    data.sequence_remove_centre = sequence.remove_centre;
+
+                    self.sequence = Some(sequence);
+
+                    data.zoom_out_enabled = true;
+                    data.repeat_flag.store(true, Ordering::SeqCst);
+
+                    // A new reference is needed at the location of the sequence
+                    ctx.submit_command(RESET_RENDERER_FULL);
+                    return;
+                }
+
                 if command.is(ZOOM_OUT_OPTIMISED) {
                     renderer.remove_centre = true;
 
@@ -813,7 +1694,7 @@ impl Widget<FractalData> for FractalWidget {
                     renderer.zoom_scale_factor = data.zoom_scale_factor;
 
                     data.remove_centre = true;
-                    settings.set("remove_centre", true).unwrap();
+                    settings.remove_centre = true;
 
                     data.zoom_out_enabled = true;
                     data.repeat_flag.store(true, Ordering::SeqCst);
@@ -844,13 +1725,13 @@ impl Widget<FractalData> for FractalWidget {
                             }
                         };
 
-                        settings.set("coloring_type", coloring_string).unwrap();
+                        settings.coloring_type = coloring_string.to_string();
 
                         renderer.data_export.lock().data_type = pixel_data_type;
 
                         if renderer.data_type == DataType::DistanceStripe {
                             renderer.data_export.lock().regenerate();
-                            ctx.submit_command(REPAINT);
+                            ctx.submit_command(REPAINT.with(data.render_job));
                             data.coloring_type = *coloring_method;
                             return;
                         }
@@ -860,7 +1741,7 @@ impl Widget<FractalData> for FractalWidget {
                             ctx.submit_command(RESET_RENDERER_FAST);
                         } else {
                             renderer.data_export.lock().regenerate();
-                            ctx.submit_command(REPAINT);
+                            ctx.submit_command(REPAINT.with(data.render_job));
                         }
                     }
 
@@ -871,7 +1752,9 @@ impl Widget<FractalData> for FractalWidget {
                 if let Some(rotation) = command.get(SET_ROTATION) {
                     let new_rotate = (rotation % 360.0 + 360.0) % 360.0;
 
-                    settings.set("rotate", new_rotate).unwrap();
+                    self.history.push(LocationSnapshot::from_settings(&settings));
+
+                    settings.rotate = new_rotate;
                     data.rotation = new_rotate;
 
                     renderer.rotate = new_rotate.to_radians();
@@ -881,21 +1764,21 @@ impl Widget<FractalData> for FractalWidget {
                 }
 
                 if command.is(SET_OFFSET_SPAN) {
-                    let current_palette_iteration_span = settings.get_float("palette_iteration_span").unwrap();
-                    let current_palette_offset = settings.get_float("palette_offset").unwrap();
-                    let current_cyclic = settings.get_bool("palette_cyclic").unwrap();
-                    let current_stripe_scale = settings.get_float("stripe_scale").unwrap() as f32;
-                    let current_distance_transition = settings.get_float("distance_transition").unwrap() as f32;
-                    let current_distance_color = settings.get_bool("distance_color").unwrap();
-
-                    let current_lighting = settings.get_bool("lighting").unwrap();
-                    let current_lighting_direction = settings.get_float("lighting_direction").unwrap();
-                    let current_lighting_azimuth = settings.get_float("lighting_azimuth").unwrap();
-                    let current_lighting_opacity = settings.get_float("lighting_opacity").unwrap();
-                    let current_lighting_ambient = settings.get_float("lighting_ambient").unwrap();
-                    let current_lighting_diffuse = settings.get_float("lighting_diffuse").unwrap();
-                    let current_lighting_specular = settings.get_float("lighting_specular").unwrap();
-                    let current_lighting_shininess = settings.get_int("lighting_shininess").unwrap();
+                    let current_palette_iteration_span = settings.palette_iteration_span;
+                    let current_palette_offset = settings.palette_offset;
+                    let current_cyclic = settings.palette_cyclic;
+                    let current_stripe_scale = settings.stripe_scale as f32;
+                    let current_distance_transition = settings.distance_transition as f32;
+                    let current_distance_color = settings.distance_color;
+
+                    let current_lighting = settings.lighting;
+                    let current_lighting_direction = settings.lighting_direction;
+                    let current_lighting_azimuth = settings.lighting_azimuth;
+                    let current_lighting_opacity = settings.lighting_opacity;
+                    let current_lighting_ambient = settings.lighting_ambient;
+                    let current_lighting_diffuse = settings.lighting_diffuse;
+                    let current_lighting_specular = settings.lighting_specular;
+                    let current_lighting_shininess = settings.lighting_shininess;
 
                     let mut changed = false;
 
@@ -906,13 +1789,13 @@ impl Widget<FractalData> for FractalWidget {
                         || !float_eq!(current_distance_transition, data.distance_transition, ulps <= 4)
                         || current_lighting != data.lighting 
                         || current_distance_color != data.distance_color {
-                        settings.set("palette_iteration_span", data.palette_iteration_span).unwrap();
-                        settings.set("palette_offset", data.palette_offset).unwrap();
-                        settings.set("palette_cyclic", data.palette_cyclic).unwrap();
-                        settings.set("stripe_scale", data.stripe_scale as f64).unwrap();
-                        settings.set("distance_transition", data.distance_transition as f64).unwrap();
-                        settings.set("distance_color", data.distance_color).unwrap();
-                        settings.set("lighting", data.lighting).unwrap();
+                        settings.palette_iteration_span = data.palette_iteration_span;
+                        settings.palette_offset = data.palette_offset;
+                        settings.palette_cyclic = data.palette_cyclic;
+                        settings.stripe_scale = data.stripe_scale as f64;
+                        settings.distance_transition = data.distance_transition as f64;
+                        settings.distance_color = data.distance_color;
+                        settings.lighting = data.lighting;
                         
                         renderer.data_export.lock().change_palette(None, data.palette_iteration_span as f32, data.palette_offset as f32, data.distance_transition, data.distance_color, data.palette_cyclic, data.lighting);
 
@@ -926,13 +1809,13 @@ impl Widget<FractalData> for FractalWidget {
                         || !float_eq!(current_lighting_diffuse, data.lighting_diffuse, ulps <= 4)
                         || !float_eq!(current_lighting_specular, data.lighting_specular, ulps <= 4)
                         || current_lighting_shininess != data.lighting_shininess {
-                        settings.set("lighting_direction", data.lighting_direction).unwrap();
-                        settings.set("lighting_azimuth", data.lighting_azimuth).unwrap();
-                        settings.set("lighting_opacity", data.lighting_opacity).unwrap();
-                        settings.set("lighting_ambient", data.lighting_ambient).unwrap();
-                        settings.set("lighting_diffuse", data.lighting_diffuse).unwrap();
-                        settings.set("lighting_specular", data.lighting_specular).unwrap();
-                        settings.set("lighting_shininess", data.lighting_shininess).unwrap();
+                        settings.lighting_direction = data.lighting_direction;
+                        settings.lighting_azimuth = data.lighting_azimuth;
+                        settings.lighting_opacity = data.lighting_opacity;
+                        settings.lighting_ambient = data.lighting_ambient;
+                        settings.lighting_diffuse = data.lighting_diffuse;
+                        settings.lighting_specular = data.lighting_specular;
+                        settings.lighting_shininess = data.lighting_shininess;
 
                         renderer.data_export.lock().change_lighting(data.lighting_direction as f32, data.lighting_azimuth as f32, data.lighting_opacity as f32, data.lighting_ambient as f32, data.lighting_diffuse as f32, data.lighting_specular as f32, data.lighting_shininess as i32);
 
@@ -946,7 +1829,7 @@ impl Widget<FractalData> for FractalWidget {
                     if float_eq!(current_stripe_scale, data.stripe_scale, ulps <= 4) {
                         renderer.data_export.lock().regenerate();
                         ctx.submit_command(UPDATE_PALETTE);
-                        ctx.submit_command(REPAINT);
+                        ctx.submit_command(REPAINT.with(data.render_job));
                     } else {
                         // If this value is changed we need to recalculate
                         renderer.data_export.lock().stripe_scale = data.stripe_scale;
@@ -958,8 +1841,6 @@ impl Widget<FractalData> for FractalWidget {
                 }
 
                 if command.is(RESET_RENDERER_FAST) {
-                    renderer.maximum_iteration = settings.get_int("iterations").unwrap() as usize;
-
                     if data.need_full_rerender {
                         // println!("needs full rerender");
                         data.need_full_rerender = false;
@@ -967,18 +1848,18 @@ impl Widget<FractalData> for FractalWidget {
                         return;
                     }
 
-                    renderer.data_type = match settings.get_str("coloring_type").unwrap().to_ascii_uppercase().as_ref() {
-                        "SMOOTH_ITERATION" | "SMOOTH" | "STEP_ITERATION" | "STEP" => DataType::Iteration,
-                        "STRIPE" => DataType::Stripe,
-                        "DISTANCE_STRIPE" => DataType::DistanceStripe,
-                        _ => DataType::Distance
-                    };
+                    // The render thread sets the size, iterations and data type from the settings
+                    data.render_job += 1;
+                    data.rendering_stage = RenderStage::Reference;
+                    data.render_progress = RenderProgress::new(data.render_job);
+
+                    let frame = self.next_sequence_frame(data);
 
                     let sender = data.sender.lock();
-                    sender.send(THREAD_RESET_RENDERER_FAST).unwrap();
+                    sender.send(ThreadCommand::ResetRendererFast { job: data.render_job, settings: settings.clone(), frame }).unwrap();
 
-                    data.image_width = settings.get_int("image_width").unwrap() as usize;
-                    data.image_height = settings.get_int("image_height").unwrap() as usize;
+                    data.image_width = settings.image_width;
+                    data.image_height = settings.image_height;
                     data.min_valid_iterations = 1;
                     data.max_valid_iterations = 1;
                     data.min_iterations = 1;
@@ -988,11 +1869,20 @@ impl Widget<FractalData> for FractalWidget {
                 }
 
                 if command.is(RESET_RENDERER_FULL) {
+                    data.render_job += 1;
+                    data.rendering_stage = RenderStage::Reference;
+                    data.render_progress = RenderProgress::new(data.render_job);
+
+                    let frame = self.next_sequence_frame(data);
+
+                    // Frames of a zoom sequence are not previewed, as they are saved rather than explored
+                    let preview = data.preview_enabled && frame.is_none() && !data.zoom_out_enabled;
+
                     let sender = data.sender.lock();
-                    sender.send(THREAD_RESET_RENDERER_FULL).unwrap();
+                    sender.send(ThreadCommand::ResetRendererFull { job: data.render_job, settings: settings.clone(), frame, preview }).unwrap();
 
-                    data.image_width = settings.get_int("image_width").unwrap() as usize;
-                    data.image_height = settings.get_int("image_height").unwrap() as usize;
+                    data.image_width = settings.image_width;
+                    data.image_height = settings.image_height;
                     data.min_valid_iterations = 1;
                     data.max_valid_iterations = 1;
                     data.min_iterations = 1;
@@ -1008,8 +1898,15 @@ impl Widget<FractalData> for FractalWidget {
                 }
 
                 if command.is(CALCULATE_ROOT) {
+                    if !data.fractal_type.supports_root_finding() {
+                        ctx.submit_command(NOTIFY.with(Notification::warning("Root finding is not supported for this formula")));
+                        return;
+                    }
+
                     data.root_stage = 1;
 
+                    self.root_snapshot = Some(LocationSnapshot::from_settings(&settings));
+
                     let size = ctx.size().to_rect();
 
                     let top_left = (self.pos1.0.min(self.pos2.0), self.pos1.1.min(self.pos2.1));
@@ -1061,26 +1958,79 @@ impl Widget<FractalData> for FractalWidget {
                     renderer.period_finding = BoxPeriod::new(box_center, [element1, element2, element3, element4]);
                     renderer.root_zoom_factor = data.root_zoom_factor;
 
-                    data.sender.lock().send(THREAD_CALCULATE_ROOT).unwrap();
+                    data.render_job += 1;
+                    data.sender.lock().send(ThreadCommand::CalculateRoot { job: data.render_job, settings: settings.clone() }).unwrap();
 
                     return;
                 }
 
                 if command.is(OPEN_LOCATION) {
                     let toml = FileSpec::new("configuration", &["toml"]);
+                    let kfr = FileSpec::new("Kalles Fraktaler location", &["kfr"]);
+                    let png = FileSpec::new("Portable Network Graphics", &["png"]);
+                    let palette = FileSpec::new("palette", PALETTE_EXTENSIONS);
+                    let iteration_map = FileSpec::new("Iteration map", &[ITERATION_MAP_EXTENSION]);
+
+                    let open_dialog_options = FileDialogOptions::new()
+                        .allowed_types(vec![toml, kfr, png, palette, iteration_map]);
+
+                    ctx.submit_command(SHOW_OPEN_PANEL.with(open_dialog_options));
+                    return;
+                }
+
+                if command.is(LOAD_PALETTE) {
+                    let palette = FileSpec::new("palette", PALETTE_EXTENSIONS);
+                    let map = FileSpec::new("Fractint map", &["map"]);
+                    let ggr = FileSpec::new("GIMP gradient", &["ggr"]);
+                    let kf = FileSpec::new("Kalles Fraktaler", &["kfp", "kfr"]);
+                    let ugr = FileSpec::new("UltraFractal gradient", &["ugr"]);
 
                     let open_dialog_options = FileDialogOptions::new()
-                        .allowed_types(vec![toml]);
+                        .allowed_types(vec![palette, map, ggr, kf, ugr])
+                        .accept_command(OPEN_PALETTE_FILE);
 
                     ctx.submit_command(SHOW_OPEN_PANEL.with(open_dialog_options));
                     return;
                 }
 
+                if let Some(file_info) = command.get(OPEN_PALETTE_FILE) {
+                    let imported = match load_palette_file(file_info.path()) {
+                        Ok(imported) => imported,
+                        Err(e) => {
+                            ctx.submit_command(NOTIFY.with(Notification::error(format!("Error reading palette: {}", e))));
+                            return;
+                        }
+                    };
+
+                    if let Some(palette_iteration_span) = imported.iteration_span {
+                        settings.palette_iteration_span = palette_iteration_span;
+                        data.palette_iteration_span = palette_iteration_span;
+                    }
+
+                    if let Some(palette_offset) = imported.offset {
+                        settings.palette_offset = palette_offset;
+                        data.palette_offset = palette_offset;
+                    }
+
+                    let mut data_export = renderer.data_export.lock();
+
+                    apply_palette(&mut data_export, &settings, &imported.palette);
+                    data_export.regenerate();
+
+                    data.palette = imported.palette;
+                    data.palette_source = file_info.path().file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+
+                    ctx.submit_command(UPDATE_PALETTE);
+                    ctx.submit_command(REPAINT.with(data.render_job));
+                    return;
+                }
+
                 if command.is(SAVE_LOCATION) {
                     let toml = FileSpec::new("configuration", &["toml"]);
+                    let kfr = FileSpec::new("Kalles Fraktaler location", &["kfr"]);
 
                     let save_dialog_options = FileDialogOptions::new()
-                        .allowed_types(vec![toml]);
+                        .allowed_types(vec![toml, kfr]);
 
                     self.save_type = 0;
 
@@ -1090,9 +2040,10 @@ impl Widget<FractalData> for FractalWidget {
 
                 if command.is(SAVE_ALL) {
                     let toml = FileSpec::new("configuration", &["toml"]);
+                    let kfr = FileSpec::new("Kalles Fraktaler location", &["kfr"]);
 
                     let save_dialog_options = FileDialogOptions::new()
-                        .allowed_types(vec![toml]);
+                        .allowed_types(vec![toml, kfr]);
 
                     self.save_type = 1;
 
@@ -1103,9 +2054,10 @@ impl Widget<FractalData> for FractalWidget {
                 if command.is(SAVE_IMAGE) {
                     let png = FileSpec::new("Portable Network Graphics", &["png"]);
                     let jpg = FileSpec::new("JPEG", &["jpg"]);
+                    let exr = FileSpec::new("OpenEXR with all channels", &["exr"]);
 
                     let save_dialog_options = FileDialogOptions::new()
-                        .allowed_types(vec![png, jpg]);
+                        .allowed_types(vec![png, jpg, exr]);
 
                     self.save_type = 2;
 
@@ -1113,174 +2065,259 @@ impl Widget<FractalData> for FractalWidget {
                     return;
                 }
 
-                if command.is(RESET_DEFAULT_LOCATION) {
-                    let mut new_settings = Config::default();
-                    new_settings.merge(File::with_name("start.toml")).unwrap();
+                if command.is(EXPORT_ITERATION_MAP) {
+                    let iteration_map = FileSpec::new("Iteration map", &[ITERATION_MAP_EXTENSION]);
 
-                    settings.set("real", new_settings.get_str("real").unwrap()).unwrap();
-                    settings.set("imag", new_settings.get_str("imag").unwrap()).unwrap();
-                    settings.set("zoom", new_settings.get_str("zoom").unwrap()).unwrap();
-                    settings.set("iterations", new_settings.get_int("iterations").unwrap()).unwrap();
-                    settings.set("rotate", new_settings.get_float("rotate").unwrap()).unwrap();
+                    let save_dialog_options = FileDialogOptions::new()
+                        .allowed_types(vec![iteration_map])
+                        .accept_command(SAVE_ITERATION_MAP);
 
-                    data.real = settings.get_str("real").unwrap();
-                    data.imag = settings.get_str("imag").unwrap();
-                    data.zoom = settings.get_str("zoom").unwrap().to_uppercase();
-                    data.iteration_limit = settings.get_int("iterations").unwrap() as usize;
-                    data.rotation = settings.get_float("rotate").unwrap();
+                    ctx.submit_command(SHOW_SAVE_PANEL.with(save_dialog_options));
+                    return;
+                }
 
-                    ctx.submit_command(RESET_RENDERER_FULL);
+                if let Some(file_info) = command.get(SAVE_ITERATION_MAP) {
+                    let location = serialize_settings(&settings, &data.palette);
+
+                    if let Err(e) = save_iteration_map(file_info.path(), &renderer.data_export.lock(), &location) {
+                        ctx.submit_command(NOTIFY.with(Notification::error(format!("Error writing {}: {}", file_info.path().display(), e))));
+                    }
+
+                    return;
                 }
 
-                if let Some(file_info) = command.get(OPEN_FILE) {
-                    settings.set("export", "gui").unwrap();
+                if command.is(IMPORT_ITERATION_MAP) {
+                    let iteration_map = FileSpec::new("Iteration map", &[ITERATION_MAP_EXTENSION]);
+
+                    let open_dialog_options = FileDialogOptions::new()
+                        .allowed_types(vec![iteration_map])
+                        .accept_command(OPEN_ITERATION_MAP);
+
+                    ctx.submit_command(SHOW_OPEN_PANEL.with(open_dialog_options));
+                    return;
+                }
+
+                if let Some(file_info) = command.get(OPEN_ITERATION_MAP) {
+                    let iteration_map = match load_iteration_map(file_info.path()) {
+                        Ok(iteration_map) => iteration_map,
+                        Err(e) => {
+                            ctx.submit_command(NOTIFY.with(Notification::error(format!("Error reading {}: {}", file_info.path().display(), e))));
+                            return;
+                        }
+                    };
 
-                    let mut new_settings = Config::default();
-                    new_settings.merge(File::with_name(file_info.path().to_str().unwrap())).unwrap();
+                    let new_settings = match parse_location(&iteration_map.location) {
+                        Ok(new_settings) => new_settings,
+                        Err(e) => {
+                            ctx.submit_command(NOTIFY.with(Notification::error(format!("Error reading location in {}: {}", file_info.path().display(), e))));
+                            return;
+                        }
+                    };
 
-                    let file_name = file_info.path().file_name().unwrap().to_str().unwrap().split('.').next().unwrap();
+                    let mut merged = settings.clone();
 
-                    let mut reset_renderer = false;
-                    let mut quick_reset = false;
+                    let loaded = match merge_location(&mut merged, new_settings) {
+                        Ok(loaded) => loaded,
+                        Err(e) => {
+                            ctx.submit_command(NOTIFY.with(Notification::error(format!("Error in {}: {}", file_info.path().display(), e))));
+                            return;
+                        }
+                    };
 
-                    if let Ok(real) = new_settings.get_str("real") {
-                        settings.set("real", real.clone()).unwrap();
-                        data.real = real;
-                        reset_renderer = true;
-                    }
+                    // The stored pixels always take priority over the size in the location
+                    merged.image_width = iteration_map.width;
+                    merged.image_height = iteration_map.height;
 
-                    if let Ok(imag) = new_settings.get_str("imag") {
-                        settings.set("imag", imag.clone()).unwrap();
-                        data.imag = imag;
-                        reset_renderer = true;
+                    // The pixel inspector and zoom box find coordinates from the location and size of the renderer
+                    if let Err(e) = set_renderer_location(&mut renderer, &merged) {
+                        ctx.submit_command(NOTIFY.with(Notification::error(format!("Error in location of {}: {}", file_info.path().display(), e))));
+                        return;
                     }
 
-                    if let Ok(zoom) = new_settings.get_str("zoom") {
-                        settings.set("zoom", zoom.clone()).unwrap();
-                        data.zoom = zoom.to_uppercase();
+                    self.history.push(LocationSnapshot::from_settings(&settings));
+                    *settings = merged;
 
-                        reset_renderer = true;
+                    for warning in &loaded.warnings {
+                        ctx.submit_command(NOTIFY.with(Notification::warning(format!("{}: {}", file_info.path().display(), warning))));
                     }
 
-                    if let Ok(iterations) = new_settings.get_int("iterations") {
-                        settings.set("iterations", iterations).unwrap();
-                        data.iteration_limit = iterations as usize;
-                        reset_renderer = true;
+                    data.real = settings.real.clone();
+                    data.imag = settings.imag.clone();
+                    data.zoom = settings.zoom.to_uppercase();
+                    data.iteration_limit = settings.iterations;
+                    data.rotation = settings.rotate;
+
+                    // Formulas that cannot be chosen in the GUI fall back to the quadratic Mandelbrot set
+                    data.fractal_type = FractalType::from_settings(&settings);
+                    data.fractal_type.apply(&mut settings);
+
+                    data.image_width = iteration_map.width;
+                    data.image_height = iteration_map.height;
+                    data.order = settings.approximation_order;
+
+                    // Only the coloring methods that use the stored values are possible
+                    let coloring_type = match (iteration_map.data_type(), data.coloring_type) {
+                        (DataType::Iteration, ColoringType::SmoothIteration) | (DataType::Iteration, ColoringType::StepIteration) => data.coloring_type,
+                        (DataType::Iteration, _) => ColoringType::SmoothIteration,
+                        (DataType::Distance, ColoringType::Stripe) | (DataType::Distance, ColoringType::DistanceStripe) => ColoringType::Distance,
+                        (DataType::Stripe, ColoringType::Distance) | (DataType::Stripe, ColoringType::DistanceStripe) => ColoringType::Stripe,
+                        _ => data.coloring_type
+                    };
+
+                    let coloring_string = match coloring_type {
+                        ColoringType::SmoothIteration => "smooth",
+                        ColoringType::StepIteration => "step",
+                        ColoringType::Stripe => "stripe",
+                        ColoringType::DistanceStripe => "distance_stripe",
+                        _ => "distance"
+                    };
+
+                    settings.coloring_type = coloring_string.to_string();
+                    data.coloring_type = coloring_type;
+
+                    let mut data_export = renderer.data_export.lock();
+
+                    data_export.coloring_type = coloring_type;
+                    data_export.maximum_iteration = data.iteration_limit;
+
+                    if let Some(palette) = loaded.palette {
+                        data.palette_iteration_span = settings.palette_iteration_span;
+                        data.palette_offset = settings.palette_offset;
+
+                        apply_palette(&mut data_export, &settings, &palette);
+
+                        data.palette = palette;
+                        data.palette_source = file_info.path().file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+
+                        ctx.submit_command(UPDATE_PALETTE);
                     }
 
-                    if let Ok(rotate) = new_settings.get_float("rotate") {
-                        settings.set("rotate", rotate).unwrap();
-                        data.rotation = rotate;
-                        reset_renderer = true;
+                    iteration_map.fill(&mut data_export);
+                    data_export.regenerate();
+
+                    let minimum_iteration = *data_export.iterations.iter().min().unwrap() as usize;
+
+                    data.min_iterations = if minimum_iteration != 0xFFFFFFFF {
+                        minimum_iteration
                     } else {
-                        settings.set("rotate", 0.0).unwrap();
-                        data.rotation = 0.0;
-                    }
+                        1
+                    };
 
-                    if let Ok(width) = new_settings.get_int("image_width") {
-                        data.image_width = width as usize;
-                        settings.set("image_width", width).unwrap();
-                        quick_reset = true;
-                    }
+                    data.max_iterations = min(*data_export.iterations.iter().max().unwrap() as usize, data.iteration_limit);
+
+                    // The reference of the renderer is still for the previous location
+                    data.need_full_rerender = true;
+
+                    ctx.submit_command(REPAINT.with(data.render_job));
+                    return;
+                }
+
+                if command.is(RESET_DEFAULT_LOCATION) {
+                    let new_settings = match load_settings(Path::new("start.toml")) {
+                        Ok((new_settings, _, warnings)) => {
+                            for warning in warnings {
+                                ctx.submit_command(NOTIFY.with(Notification::warning(warning)));
+                            }
+
+                            new_settings
+                        },
+                        Err(e) => {
+                            ctx.submit_command(NOTIFY.with(Notification::error(e)));
+                            return;
+                        }
+                    };
+
+                    self.history.push(LocationSnapshot::from_settings(&settings));
+
+                    settings.real = new_settings.real;
+                    settings.imag = new_settings.imag;
+                    settings.zoom = new_settings.zoom;
+                    settings.iterations = new_settings.iterations;
+                    settings.rotate = new_settings.rotate;
+
+                    data.real = settings.real.clone();
+                    data.imag = settings.imag.clone();
+                    data.zoom = settings.zoom.to_uppercase();
+                    data.iteration_limit = settings.iterations;
+                    data.rotation = settings.rotate;
+
+                    ctx.submit_command(RESET_RENDERER_FULL);
+                }
 
-                    if let Ok(height) = new_settings.get_int("image_height") {
-                        data.image_height = height as usize;
-                        settings.set("image_height", height).unwrap();
-                        quick_reset = true;
+                if let Some(file_info) = command.get(OPEN_FILE) {
+                    if is_palette_file(file_info.path()) {
+                        ctx.submit_command(OPEN_PALETTE_FILE.with(file_info.clone()));
+                        return;
                     }
 
-                    if let Ok(order) = new_settings.get_int("approximation_order") {
-                        data.order = order as usize;
-                        settings.set("approximation_order", order).unwrap();
-                        quick_reset = true;
+                    if is_iteration_map_file(file_info.path()) {
+                        ctx.submit_command(OPEN_ITERATION_MAP.with(file_info.clone()));
+                        return;
                     }
 
-                    // if let Ok(analytic_derivative) = new_settings.get_bool("analytic_derivative") {
-                    //     // TODO need to change the data type
-                    //     renderer.data_export.lock().coloring_type = if analytic_derivative {
-                    //         ColoringType::Distance
-                    //     } else {
-                    //         data.coloring_type
-                    //     };
+                    let new_settings = match load_location_file(file_info.path()) {
+                        Ok(new_settings) => new_settings,
+                        Err(e) => {
+                            ctx.submit_command(NOTIFY.with(Notification::error(format!("Error reading {}: {}", file_info.path().display(), e))));
+                            return;
+                        }
+                    };
 
-                    //     renderer.data_export.lock().data_type = if analytic_derivative {
-                    //         DataType::Distance
-                    //     } else {
-                    //         DataType::Iteration
-                    //     };
+                    let file_name = file_info.path().file_name()
+                        .map(|name| name.to_string_lossy().split('.').next().unwrap().to_string())
+                        .unwrap_or_default();
 
-                    //     settings.set("analytic_derivative", analytic_derivative).unwrap();
-                    //     quick_reset = true;
-                    // }
+                    let snapshot = LocationSnapshot::from_settings(&settings);
 
-                    if let Ok(colour_values) = new_settings.get_array("palette") {
-                        // Only reset these if the palette is defined
-                        match new_settings.get_float("palette_iteration_span") {
-                            Ok(palette_iteration_span) => {
-                                settings.set("palette_iteration_span", palette_iteration_span).unwrap();
-                                data.palette_iteration_span = palette_iteration_span;
-                            }
-                            Err(_) => {
-                                settings.set("palette_iteration_span", 1.0).unwrap();
-                                data.palette_iteration_span = 1.0;
-                            }
-                        }
-    
-                        match new_settings.get_float("palette_offset") {
-                            Ok(palette_offset) => {
-                                settings.set("palette_offset", palette_offset).unwrap();
-                                data.palette_offset = palette_offset;
-                            }
-                            Err(_) => {
-                                settings.set("palette_offset", 0.0).unwrap();
-                                data.palette_offset = 0.0;
-                            }
+                    let loaded = match merge_location(&mut settings, new_settings) {
+                        Ok(loaded) => loaded,
+                        Err(e) => {
+                            ctx.submit_command(NOTIFY.with(Notification::error(format!("Error in {}: {}", file_info.path().display(), e))));
+                            return;
                         }
+                    };
+
+                    self.history.push(snapshot);
 
-                        settings.set("palette", colour_values.clone()).unwrap();
-
-                        let palette = colour_values.chunks_exact(3).map(|value| {
-                            // We assume the palette is in BGR rather than RGB
-                            (value[0].clone().into_int().unwrap() as u8, 
-                                value[1].clone().into_int().unwrap() as u8, 
-                                value[2].clone().into_int().unwrap() as u8)
-                        }).collect::<Vec<(u8, u8, u8)>>();
-
-                        renderer.data_export.lock().change_palette(
-                            Some(palette),
-                            settings.get_float("palette_iteration_span").unwrap() as f32,
-                            settings.get_float("palette_offset").unwrap() as f32,
-                            settings.get_float("distance_transition").unwrap() as f32,
-                            settings.get_bool("distance_color").unwrap(),
-                            settings.get_bool("palette_cyclic").unwrap(),
-                            settings.get_bool("lighting").unwrap()
-                        );
-
-                        renderer.data_export.lock().change_lighting(
-                            settings.get_float("lighting_direction").unwrap() as f32,
-                            settings.get_float("lighting_azimuth").unwrap() as f32,
-                            settings.get_float("lighting_opacity").unwrap() as f32,
-                            settings.get_float("lighting_ambient").unwrap() as f32,
-                            settings.get_float("lighting_diffuse").unwrap() as f32,
-                            settings.get_float("lighting_specular").unwrap() as f32,
-                            settings.get_float("lighting_shininess").unwrap() as i32
-                        );
-
-                        data.palette_source = file_name.to_string();
+                    for warning in &loaded.warnings {
+                        ctx.submit_command(NOTIFY.with(Notification::warning(format!("{}: {}", file_info.path().display(), warning))));
+                    }
+
+                    data.real = settings.real.clone();
+                    data.imag = settings.imag.clone();
+                    data.zoom = settings.zoom.to_uppercase();
+                    data.iteration_limit = settings.iterations;
+                    data.rotation = settings.rotate;
+
+                    // Formulas that cannot be chosen in the GUI fall back to the quadratic Mandelbrot set
+                    data.fractal_type = FractalType::from_settings(&settings);
+                    data.fractal_type.apply(&mut settings);
+
+                    data.image_width = settings.image_width;
+                    data.image_height = settings.image_height;
+                    data.order = settings.approximation_order;
+
+                    if let Some(palette) = loaded.palette {
+                        data.palette_iteration_span = settings.palette_iteration_span;
+                        data.palette_offset = settings.palette_offset;
+
+                        apply_palette(&mut renderer.data_export.lock(), &settings, &palette);
+
+                        data.palette = palette;
+
+                        data.palette_source = file_name;
 
                         ctx.submit_command(UPDATE_PALETTE);
 
-                        if !reset_renderer || !quick_reset {
+                        if !loaded.reset_renderer || !loaded.quick_reset {
                             renderer.data_export.lock().regenerate();
-                            ctx.submit_command(REPAINT);
+                            ctx.submit_command(REPAINT.with(data.render_job));
                         }
                     }
 
-                    settings.merge(new_settings).unwrap();
-
-                    if reset_renderer {
+                    if loaded.reset_renderer {
                         ctx.submit_command(RESET_RENDERER_FULL);
-                    } else if quick_reset {
+                    } else if loaded.quick_reset {
                         ctx.submit_command(RESET_RENDERER_FAST);
                     }
 
@@ -1288,68 +2325,89 @@ impl Widget<FractalData> for FractalWidget {
                 }
 
                 if let Some(file_info) = command.get(SAVE_FILE_AS) {
+                    // Kalles Fraktaler locations always include the palette
+                    if self.save_type < 2 && is_kfr_file(file_info.path()) {
+                        if let Err(e) = std::fs::write(file_info.path(), location_to_kfr(&settings, &data.palette)) {
+                            ctx.submit_command(NOTIFY.with(Notification::error(format!("Error writing {}: {}", file_info.path().display(), e))));
+                        }
+
+                        return;
+                    }
+
                     match self.save_type {
                         0 => {
-                            let real = settings.get_str("real").unwrap();
-                            let imag = settings.get_str("imag").unwrap();
-                            let zoom = settings.get_str("zoom").unwrap();
-                            let iterations = settings.get_int("iterations").unwrap();
-                            let rotate = settings.get_float("rotate").unwrap();
+                            let real = settings.real.clone();
+                            let imag = settings.imag.clone();
+                            let zoom = settings.zoom.clone();
+                            let iterations = settings.iterations;
+                            let rotate = settings.rotate;
+                            let fractal_type = toml_string(&settings.fractal_type);
+                            let fractal_power = settings.fractal_power;
 
-                            let output = format!("real = \"{}\"\nimag = \"{}\"\nzoom = \"{}\"\niterations = {}\nrotate = {}", real, imag, zoom, iterations.to_string(), rotate.to_string());
+                            let output = format!("real = \"{}\"\nimag = \"{}\"\nzoom = \"{}\"\niterations = {}\nrotate = {}\nfractal_type = {}\nfractal_power = {}", real, imag, zoom, iterations.to_string(), rotate.to_string(), fractal_type, fractal_power.to_string());
 
                             if let Err(e) = std::fs::write(file_info.path(), output) {
-                                println!("Error writing file: {}", e);
+                                ctx.submit_command(NOTIFY.with(Notification::error(format!("Error writing {}: {}", file_info.path().display(), e))));
                             }
                         },
                         1 => {
-                            let real = settings.get_str("real").unwrap();
-                            let imag = settings.get_str("imag").unwrap();
-                            let zoom = settings.get_str("zoom").unwrap();
-                            let iterations = settings.get_int("iterations").unwrap();
-                            let rotate = settings.get_float("rotate").unwrap();
-
-                            let image_width = settings.get_int("image_width").unwrap();
-                            let image_height = settings.get_int("image_height").unwrap();
-                            let glitch_percentage = settings.get_float("glitch_percentage").unwrap();
-                            let approximation_order = settings.get_int("approximation_order").unwrap();
-                            let coloring_type = settings.get_str("coloring_type").unwrap();
-
-                            let palette = renderer.data_export.lock().palette_interpolated_buffer.clone().into_iter().flat_map(|seq| {
-                                let (r, g, b, _) = seq.rgba_u8();
-                                vec![r, g, b]
-                            }).collect::<Vec<u8>>();
-                            let palette_iteration_span = settings.get_float("palette_iteration_span").unwrap();
-                            let palette_offset = settings.get_float("palette_offset").unwrap();
-
-                            let output = format!(
-                                "version = \"{}\"\n\nreal = \"{}\"\nimag = \"{}\"\nzoom = \"{}\"\niterations = {}\nrotate = {}\n\nimage_width = {}\nimage_height = {}\nglitch_percentage = {}\napproximation_order = {}\ncoloring_type = {}\nframes = 1\nframe_offset = 0\nzoom_scale = 2.0\ndisplay_glitches = false\nauto_adjust_iterations = true\nremove_centre = false\nglitch_tolerance = 1.4e-6\nprobe_sampling = 15\ndata_storage_interval = 100\nvalid_iteration_frame_multiplier = 0.10\nvalid_iteration_probe_multiplier = 0.01\nseries_approximation_tiled = true\njitter = false\nexport = \"png\"\n\npalette = {:?}\npalette_iteration_span = {}\npalette_offset = {}", 
-                                env!("CARGO_PKG_VERSION"),
-                                real, 
-                                imag, 
-                                zoom, 
-                                iterations.to_string(), 
-                                rotate.to_string(),
-                                image_width,
-                                image_height,
-                                glitch_percentage,
-                                approximation_order,
-                                coloring_type,
-                                palette,
-                                palette_iteration_span,
-                                palette_offset);
+                            let output = serialize_settings(&settings, &data.palette);
 
                             if let Err(e) = std::fs::write(file_info.path(), output) {
-                                println!("Error writing file: {}", e);
+                                ctx.submit_command(NOTIFY.with(Notification::error(format!("Error writing {}: {}", file_info.path().display(), e))));
                             }
                         },
                         2 => {
-                            renderer.data_export.lock().save_colour(file_info.path().to_str().unwrap());
+                            if is_exr_file(file_info.path()) {
+                                if let Err(e) = save_exr(file_info.path(), &renderer.data_export.lock()) {
+                                    ctx.submit_command(NOTIFY.with(Notification::error(format!("Error writing {}: {}", file_info.path().display(), e))));
+                                }
+
+                                return;
+                            }
+
+                            match file_info.path().to_str() {
+                                Some(path) => renderer.data_export.lock().save_colour(path),
+                                None => {
+                                    ctx.submit_command(NOTIFY.with(Notification::error(format!("Error writing {}: invalid path", file_info.path().display()))));
+                                    return;
+                                }
+                            }
+
+                            // The location is stored in the image so that it can be opened again
+                            if is_png_file(file_info.path()) {
+                                if let Err(e) = write_png_text(file_info.path(), &location_text_chunks(&settings, &data.palette)) {
+                                    ctx.submit_command(NOTIFY.with(Notification::error(format!("Error writing location to {}: {}", file_info.path().display(), e))));
+                                }
+                            }
                         },
                         _ => {}
                     }
                 }
             },
+            Event::Timer(token) => {
+                if *token == self.toast_timer {
+                    data.toast = None;
+                }
+
+                if *token == self.wheel_timer {
+                    self.wheel_timer = TimerToken::INVALID;
+
+                    if self.wheel_zoom.is_none() {
+                        return;
+                    }
+
+                    // The render of a location that has been scrolled away from is stopped, and the wheel zoom
+                    // is sent when its last repaint arrives
+                    if data.rendering_stage != RenderStage::Complete {
+                        self.wheel_pending = true;
+                        ctx.submit_command(STOP_RENDERING);
+                        return;
+                    }
+
+                    self.send_wheel_zoom(ctx, data);
+                }
+            },
             _ => {}
         }
         
@@ -1361,14 +2419,11 @@ impl Widget<FractalData> for FractalWidget {
         // println!("update called");
     }
 
-    fn layout(&mut self, _layout_ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &FractalData, _env: &Env) -> Size {
+    fn layout(&mut self, _layout_ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &FractalData, _env: &Env) -> Size {
         // println!("layout called");
         let mut test = bc.max();
 
-        let mut settings = data.settings.lock();
-
-        settings.set("window_width", test.width).unwrap();
-        settings.set("window_height", test.height).unwrap();
+        self.window_size = test;
 
         let aspect_image = self.image_width as f64 / self.image_height as f64;
         let aspect_constraints = test.width / test.height;
@@ -1399,6 +2454,41 @@ impl Widget<FractalData> for FractalWidget {
 
             let mut size = ctx.size().to_rect();
 
+            if let Some(preview) = &self.preview {
+                if self.cached_preview.is_none() {
+                    self.cached_preview = Some(ctx
+                        .make_image(preview.width, preview.height, &preview.buffer, ImageFormat::Rgb)
+                        .unwrap());
+                }
+
+                let preview_position = Rect::new(0.0, 0.0, preview.width as f64, preview.height as f64);
+
+                ctx.draw_image_area(self.cached_preview.as_ref().unwrap(), preview_position, size, InterpolationMode::Bilinear);
+                return;
+            }
+
+            // The current image is scaled around the centre of the wheel zoom until the new render is ready
+            if let Some((centre, factor)) = self.wheel_zoom {
+                let scale_x = factor * size.width() / self.image_width as f64;
+                let scale_y = factor * size.height() / self.image_height as f64;
+
+                let image_position = Rect::new(
+                    size.center().x - centre.0 * scale_x,
+                    size.center().y - centre.1 * scale_y,
+                    size.center().x + (self.image_width as f64 - centre.0) * scale_x,
+                    size.center().y + (self.image_height as f64 - centre.1) * scale_y);
+
+                let image = self.cached_image.as_ref().unwrap();
+
+                ctx.with_save(|ctx| {
+                    ctx.clip(size);
+                    ctx.fill(size, &Color::BLACK);
+                    ctx.draw_image(image, image_position, InterpolationMode::Bilinear);
+                });
+
+                return;
+            }
+
             let interpolation_mode = if self.image_width > size.width() as usize || self.image_height > size.height() as usize {
                 InterpolationMode::Bilinear
             } else {
@@ -1407,7 +2497,7 @@ impl Widget<FractalData> for FractalWidget {
 
             let mut image_position = Rect::new(0.0, 0.0, self.image_width as f64, self.image_height as f64);
 
-            if self.mouse_mode != MouseMode::RootFinding {
+            if self.mouse_mode != MouseMode::RootFinding && self.mouse_mode != MouseMode::ZoomBox {
                 let x_delta = self.pos2.0 - self.pos1.0;
                 let y_delta = self.pos2.1 - self.pos1.1;
 
@@ -1436,6 +2526,13 @@ impl Widget<FractalData> for FractalWidget {
 
                 ctx.fill(circle, &fill_color);
             }
+
+            if self.mouse_mode == MouseMode::ZoomBox {
+                let rect = Rect::from_points(self.pos1, self.pos2);
+
+                ctx.fill(rect, &Color::rgba8(255, 255, 255, 40));
+                ctx.stroke(rect, &Color::rgba8(255, 255, 255, 255), 1.0);
+            }
         }
     }
 